@@ -2,35 +2,97 @@ use super::{
     ThreadKey,
 };
 
-use std::collections::{BTreeSet, BTreeMap};
+use std::collections::{BTreeSet, BTreeMap, VecDeque};
+use std::collections::btree_map::{Entry, Iter};
 
 /// Channel identifier.
 pub type Key = u32;
 
+/// Capacity of the message queue of channels created with `Channel::new`.
+pub const DEFAULT_CAPACITY: usize = 16;
+
 /// The channel-related information.
+///
+/// Each channel holds a bounded queue of messages with payload of type `M`.
 #[derive(Debug)]
-pub struct Channel {
+pub struct Channel<M = ()> {
 
     /// Participants in channel transactions.
     participants: BTreeSet<ThreadKey>,
+
+    /// Thread that created the channel.
+    creator: ThreadKey,
+
+    /// Messages that are ready to be received.
+    queue: VecDeque<M>,
+
+    /// Maximal number of messages in the queue.
+    capacity: usize,
+
+    /// Messages of the senders that are blocked because the queue is full.
+    pending: VecDeque<(ThreadKey, M)>,
+
+    /// Receivers that are blocked because the queue is empty.
+    receivers: VecDeque<ThreadKey>,
 }
 
 /// Set that contains all channels.
 #[derive(Debug)]
-pub struct ChannelSet {
-    map: BTreeMap<Key, Channel>,
+pub struct ChannelSet<M = ()> {
+    map: BTreeMap<Key, Channel<M>>,
+}
+
+/// Outcome of sending a message to the channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sent {
+
+    /// Message was put in the queue. Contains the receiver that was
+    /// waiting for a message and was woken up, if any.
+    Queued(Option<ThreadKey>),
+
+    /// Queue is full. Sender waits until the message is taken into the
+    /// queue.
+    Blocked,
+}
+
+/// Outcome of receiving a message from the channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Received<M> {
+
+    /// Message was taken from the queue. Contains the sender that was
+    /// waiting for free space in the queue and was woken up, if any.
+    Message(M, Option<ThreadKey>),
+
+    /// Queue is empty. Receiver waits until a message is sent.
+    Blocked,
 }
 
-impl Channel {
+impl<M> Channel<M> {
 
     /// Create new channel with only given thread in it.
-    pub fn new(creator: ThreadKey) -> Channel {
+    pub fn new(creator: ThreadKey) -> Self {
+        Self::with_capacity(creator, DEFAULT_CAPACITY)
+    }
+
+    /// Create new channel with only given thread in it and given capacity
+    /// of the message queue.
+    ///
+    /// # Panics
+    /// Panic occurs if capacity is zero.
+    pub fn with_capacity(creator: ThreadKey, capacity: usize) -> Self {
+        assert!(capacity > 0, "Channel capacity must not be zero");
+
         let mut participants = BTreeSet::default();
 
         participants.insert(creator);
 
         Channel {
-            participants
+            participants,
+            creator,
+            queue: Default::default(),
+            capacity,
+            pending: Default::default(),
+            receivers: Default::default(),
         }
     }
 
@@ -39,21 +101,104 @@ impl Channel {
         &self.participants
     }
 
+    /// Thread that created the channel. It opens the channel to the other
+    /// participants and so policies are checked for it when the channel
+    /// is registered. If it leaves the channel, the first of the remaining
+    /// participants takes it's place. Snapshots do not keep it, loaded
+    /// channels are created by their first participant.
+    pub fn creator(&self) -> ThreadKey {
+        self.creator
+    }
+
     /// Try adding participant. If it is already present, false is returned.
     pub fn add_participant(&mut self, thread: ThreadKey) -> bool {
-        let present = self.participants.insert(thread);
-        present
+        self.participants.insert(thread)
     }
 
     /// Remove participant from the channel. If it was present, true is
-    /// returned.
+    /// returned. Messages of the participant that wait for free space in
+    /// the queue are dropped.
     pub fn remove_participant(&mut self, thread: ThreadKey) -> bool {
-        let present = self.participants.remove(&thread);
-        present
+        self.pending.retain(|(sender, _)| *sender != thread);
+        self.receivers.retain(|receiver| *receiver != thread);
+
+        let removed = self.participants.remove(&thread);
+        if thread == self.creator {
+            if let Some(first) = self.participants.iter().next() {
+                self.creator = *first;
+            }
+        }
+        removed
+    }
+
+    /// Messages that are ready to be received.
+    pub fn queue(&self) -> &VecDeque<M> {
+        &self.queue
+    }
+
+    /// Maximal number of messages in the queue.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Whether queue has no free space for new messages.
+    pub fn is_full(&self) -> bool {
+        self.queue.len() >= self.capacity
+    }
+
+    /// Senders that wait for free space in the queue, with their messages.
+    pub fn pending(&self) -> &VecDeque<(ThreadKey, M)> {
+        &self.pending
+    }
+
+    /// Receivers that wait for a message.
+    pub fn receivers(&self) -> &VecDeque<ThreadKey> {
+        &self.receivers
+    }
+
+    /// Whether thread waits in the queue of blocked senders or receivers.
+    pub fn is_blocked(&self, thread: &ThreadKey) -> bool {
+        self.pending.iter().any(|(sender, _)| sender == thread)
+                || self.receivers.contains(thread)
+    }
+
+    /// Put message to the queue.
+    pub(crate) fn push(&mut self, message: M) {
+        self.queue.push_back(message);
+    }
+
+    /// Take next message from the queue. If there are blocked senders,
+    /// message of the first one is moved to the queue and the sender is
+    /// returned along with the message.
+    pub(crate) fn pop(&mut self) -> Option<(M, Option<ThreadKey>)> {
+        let message = self.queue.pop_front()?;
+        let sender = match self.pending.pop_front() {
+            Some((sender, pending)) => {
+                self.queue.push_back(pending);
+                Some(sender)
+            },
+            None => None,
+        };
+        Some((message, sender))
+    }
+
+    /// Save the message of the sender that waits for free space.
+    pub(crate) fn push_pending(&mut self, sender: ThreadKey, message: M) {
+        self.pending.push_back((sender, message));
+    }
+
+    /// Save the receiver that waits for a message.
+    pub(crate) fn push_receiver(&mut self, receiver: ThreadKey) {
+        self.receivers.push_back(receiver);
+    }
+
+    /// Take the first receiver that waits for a message.
+    pub(crate) fn pop_receiver(&mut self) -> Option<ThreadKey> {
+        self.receivers.pop_front()
     }
 }
 
-impl ChannelSet {
+impl<M> ChannelSet<M> {
 
     /// Create new empty channel set.
     pub fn new() -> Self {
@@ -62,12 +207,13 @@ impl ChannelSet {
 
     /// Add new channel to the set. If it is already present, existing channel
     /// is not modified and true is returned. False otherwise.
-    pub fn insert(&mut self, key: Key, channel: Channel) -> bool {
-        if self.map.contains_key(&key) {
-            true
-        } else {
-            self.map.insert(key, channel);
-            false
+    pub fn insert(&mut self, key: Key, channel: Channel<M>) -> bool {
+        match self.map.entry(key) {
+            Entry::Occupied(_) => true,
+            Entry::Vacant(e) => {
+                e.insert(channel);
+                false
+            },
         }
     }
 
@@ -77,18 +223,23 @@ impl ChannelSet {
         self.map.remove(&key).is_some()
     }
 
+    /// Iterator over channels of the set.
+    pub fn iter(&self) -> Iter<'_, Key, Channel<M>> {
+        self.map.iter()
+    }
+
     /// Channel in the set by the key.
-    pub fn get(&self, key: &Key) -> Option<&Channel> {
-        self.map.get(&key)
+    pub fn get(&self, key: &Key) -> Option<&Channel<M>> {
+        self.map.get(key)
     }
 
     /// Channel in the set by the key.
-    pub fn get_mut(&mut self, key: &Key) -> Option<&mut Channel> {
-        self.map.get_mut(&key)
+    pub fn get_mut(&mut self, key: &Key) -> Option<&mut Channel<M>> {
+        self.map.get_mut(key)
     }
 }
 
-impl Default for ChannelSet {
+impl<M> Default for ChannelSet<M> {
 
     fn default() -> Self {
         ChannelSet {
@@ -97,3 +248,26 @@ impl Default for ChannelSet {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_queue() {
+        let mut chan = Channel::with_capacity(1, 1);
+        chan.add_participant(2);
+
+        chan.push("a");
+        assert!(chan.is_full());
+        chan.push_pending(1, "b");
+
+        assert_eq!(chan.pop(), Some(("a", Some(1))));
+        assert_eq!(chan.queue().front(), Some(&"b"));
+        assert_eq!(chan.pop(), Some(("b", None)));
+        assert_eq!(chan.pop(), None);
+
+        assert_eq!(chan.creator(), 1);
+        chan.remove_participant(1);
+        assert_eq!(chan.creator(), 2);
+    }
+}
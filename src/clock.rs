@@ -0,0 +1,51 @@
+/// Point of virtual time or duration measured in clock ticks.
+pub type Time = u64;
+
+/// Virtual clock that measures time in the network. It only moves when
+/// it is explicitly advanced.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Clock {
+    now: Time,
+}
+
+impl Clock {
+
+    /// Create new clock that starts from zero.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Current time.
+    pub fn now(&self) -> Time {
+        self.now
+    }
+
+    /// Move the clock forward by given duration and return new time.
+    pub fn advance(&mut self, dt: Time) -> Time {
+        self.now = self.now.saturating_add(dt);
+        self.now
+    }
+
+    /// Time when the timeout that starts now expires.
+    pub fn deadline(&self, timeout: Time) -> Time {
+        self.now.saturating_add(timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_advance() {
+        let mut clock = Clock::new();
+        assert_eq!(clock.now(), 0);
+        assert_eq!(clock.deadline(5), 5);
+
+        assert_eq!(clock.advance(3), 3);
+        assert_eq!(clock.deadline(5), 8);
+
+        clock.advance(Time::MAX);
+        assert_eq!(clock.now(), Time::MAX);
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::{
+    ThreadKey,
+    ChannelKey,
+    ProcessKey,
+    InterfaceKey,
+    Cycle,
+    Access,
+    Path,
+    RcPath,
+    VersionReq,
+};
+
+/// Error that may occur during operations on the network and it's parts.
+///
+/// Errors of parsing the text formats are part of the same type, because
+/// loaders of snapshots and interface definitions fail both on bad text
+/// and on the network refusing what the text describes, and callers handle
+/// both the same way. Parse errors are the variants named `Bad*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+
+    /// Thread with given key was not found.
+    UnknownThread(ThreadKey),
+
+    /// Channel with given key was not found.
+    UnknownChannel(ChannelKey),
+
+    /// Process with given key was not found.
+    UnknownProcess(ProcessKey),
+
+    /// Interface with given key was not found.
+    UnknownInterface(InterfaceKey),
+
+    /// Thread does not participate in the channel.
+    NotParticipant(ThreadKey, ChannelKey),
+
+    /// Process with given key is already registered.
+    ProcessExists(ProcessKey),
+
+    /// Interface with given key is already registered.
+    InterfaceExists(InterfaceKey),
+
+    /// Waiting for a signal would cause a deadlock. Contains the loop of
+    /// channel relations that was found.
+    Deadlock(Cycle),
+
+    /// Thread cannot become active because all processors are busy.
+    NoFreeProcessor(ThreadKey),
+
+    /// Thread belongs to no process, so policies cannot be checked for it.
+    ThreadHasNoProcess(ThreadKey),
+
+    /// Process cannot be registered because threads are already attached
+    /// to it. Threads are attached with `Network::new_thread` only.
+    ProcessHasThreads,
+
+    /// Snapshot of the network cannot be loaded. Contains the number of
+    /// the line with the problem and it's description.
+    BadSnapshot(usize, String),
+
+    /// Version requirement cannot be parsed. Contains the requirement
+    /// and description of the problem.
+    BadVersionReq(String, String),
+
+    /// Version cannot be parsed. Contains the text and description of the
+    /// problem.
+    BadVersion(String, String),
+
+    /// Path cannot be parsed. Contains the text and description of the
+    /// problem.
+    BadPath(String, String),
+
+    /// Interface key cannot be parsed. Contains the text and description
+    /// of the problem.
+    BadInterfaceKey(String, String),
+
+    /// Interface would depend on itself through it's prerequisites.
+    /// Contains the loop of interfaces, each one requires the next and
+    /// the last one requires the first.
+    PrerequisiteCycle(Vec<InterfaceKey>),
+
+    /// Interfaces cannot be compared as they have different paths or
+    /// same versions.
+    IncomparableInterfaces(InterfaceKey, InterfaceKey),
+
+    /// Interface definitions cannot be loaded. Contains the line and the
+    /// column of the problem and it's description.
+    BadIdl(usize, usize, String),
+
+    /// Function signature or type cannot be parsed. Contains the text and
+    /// description of the problem.
+    BadSignature(String, String),
+
+    /// No thread implements interface with given path and the version
+    /// that satisfies the requirement.
+    NoImplementer(RcPath, VersionReq),
+
+    /// Interface cannot be removed because it is still used. Contains the
+    /// interface, interfaces that require it and processes that implement
+    /// it.
+    InterfaceInUse(InterfaceKey, BTreeSet<InterfaceKey>,
+            BTreeSet<ProcessKey>),
+
+    /// Policies do not give the access to the interfaces. Contains the
+    /// path of the process, the kind of the access and the path of the
+    /// interface.
+    PermissionDenied(RcPath, Access, RcPath),
+}
+
+impl fmt::Display for Error {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Error::*;
+
+        match self {
+            UnknownThread(key) => write!(f, "unknown thread {}", key),
+            UnknownChannel(key) => write!(f, "unknown channel {}", key),
+            UnknownProcess(key) => write!(f, "unknown process {}", key),
+            UnknownInterface(key) => write!(f, "unknown interface {}", key),
+            NotParticipant(thread, chan) => write!(f,
+                    "thread {} does not participate in channel {}",
+                    thread, chan),
+            ProcessExists(key) => write!(f,
+                    "process {} is already registered", key),
+            InterfaceExists(key) => write!(f,
+                    "interface {} is already registered", key),
+            Deadlock(cycle) => {
+                write!(f, "deadlock in channels ")?;
+                write_cycle(f, cycle.channels())
+            },
+            NoFreeProcessor(key) => write!(f,
+                    "no free processor for thread {}", key),
+            ThreadHasNoProcess(key) => write!(f,
+                    "thread {} belongs to no process", key),
+            ProcessHasThreads => write!(f,
+                    "new process already has threads"),
+            BadSnapshot(line, reason) => write!(f,
+                    "bad snapshot at line {}: {}", line, reason),
+            BadVersionReq(req, reason) => write!(f,
+                    "bad version requirement '{}': {}", req, reason),
+            BadVersion(text, reason) => write!(f,
+                    "bad version '{}': {}", text, reason),
+            BadPath(text, reason) => write!(f,
+                    "bad path '{}': {}", text, reason),
+            BadInterfaceKey(text, reason) => write!(f,
+                    "bad interface key '{}': {}", text, reason),
+            PrerequisiteCycle(cycle) => {
+                write!(f, "prerequisite cycle ")?;
+                write_cycle(f, cycle)
+            },
+            IncomparableInterfaces(a, b) => write!(f,
+                    "interfaces {} and {} cannot be compared", a, b),
+            BadIdl(line, column, reason) => write!(f,
+                    "bad interface definition at {}:{}: {}",
+                    line, column, reason),
+            BadSignature(text, reason) => write!(f,
+                    "bad signature '{}': {}", text, reason),
+            NoImplementer(path, req) => write!(f,
+                    "no implementer of {} {}", Path::to_string(path), req),
+            InterfaceInUse(key, interfaces, processes) => {
+                write!(f, "interface {} is in use", key)?;
+                if !interfaces.is_empty() {
+                    write!(f, ", required by")?;
+                    for (i, dependent) in interfaces.iter().enumerate() {
+                        let sep = if i == 0 { " " } else { ", " };
+                        write!(f, "{}{}", sep, dependent)?;
+                    }
+                }
+                if !processes.is_empty() {
+                    write!(f, ", implemented by process")?;
+                    for (i, process) in processes.iter().enumerate() {
+                        let sep = if i == 0 { " " } else { ", " };
+                        write!(f, "{}{}", sep, process)?;
+                    }
+                }
+                Ok(())
+            },
+            PermissionDenied(process, access, target) => {
+                let action = match access {
+                    Access::Implement => "implement",
+                    Access::Connect => "connect to",
+                };
+                write!(f, "process at {} may not {} interfaces at {}",
+                        Path::to_string(process), action,
+                        Path::to_string(target))
+            },
+        }
+    }
+}
+
+impl ::std::error::Error for Error {}
+
+/// Write the loop of items where the last one leads back to the first.
+fn write_cycle<T: fmt::Display>(f: &mut fmt::Formatter, cycle: &[T])
+        -> fmt::Result {
+    let first = match cycle.first() {
+        Some(first) => first,
+        None => return write!(f, "(empty)"),
+    };
+    for item in cycle {
+        write!(f, "{} -> ", item)?;
+    }
+    write!(f, "{}", first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_cycle_display() {
+        let a: InterfaceKey = "a@1.0.0".parse().unwrap();
+        let b: InterfaceKey = "b@1.0.0".parse().unwrap();
+        let err = Error::PrerequisiteCycle(vec![a, b]);
+        assert_eq!(err.to_string(),
+                "prerequisite cycle a@1.0.0 -> b@1.0.0 -> a@1.0.0");
+
+        let err = Error::PrerequisiteCycle(Vec::new());
+        assert_eq!(err.to_string(), "prerequisite cycle (empty)");
+    }
+}
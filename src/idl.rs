@@ -0,0 +1,467 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    Error,
+    Interface,
+    InterfaceFunc,
+    InterfaceKey,
+    InterfaceSet,
+    Network,
+    PackageTree,
+    Path,
+    RcPath,
+    Signature,
+    Version,
+};
+
+/// Word or punctuation of the definition with it's position.
+#[derive(Debug)]
+struct Token {
+    text: String,
+    line: usize,
+    column: usize,
+}
+
+/// Parser of the interface definitions.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+
+    /// Position of the end of the text.
+    end: (usize, usize),
+
+    packages: Vec<RcPath>,
+    interfaces: Vec<(InterfaceKey, Interface, usize, usize)>,
+}
+
+/// Load interface definitions from the text. Returns the set of defined
+/// interfaces. Declared packages are stored in the package tree when the
+/// whole text is loaded successfully.
+///
+/// Definitions consist of packages that contain interfaces and other
+/// packages. Interface has a version, functions with versions and
+/// prerequisites which are referred by full keys. Function may have a
+/// signature, see `Signature` for the syntax. Comments start with `#`.
+///
+/// ```text
+/// package root.io;
+///
+/// package root.fs {
+///     interface File 1.2.0 {
+///         fn open 1.0.0 (path: str) -> chan;
+///         fn read 2.0.0;
+///         requires root.io.Stream@1.0.0;
+///     }
+///
+///     package local {
+///         # Interface root.fs.local.Dir@1.0.0
+///         interface Dir 1.0.0 {
+///             fn list 1.0.0;
+///         }
+///     }
+/// }
+/// ```
+///
+/// # Errors
+/// `BadIdl` is returned with the line and the column of the problem if
+/// the text is malformed or interfaces cannot be added to the set.
+pub fn load(text: &str, packages: &mut PackageTree)
+        -> Result<InterfaceSet, Error> {
+    let parser = parse(text)?;
+
+    let mut set = InterfaceSet::new();
+    parser.add_interfaces(&mut set)?;
+
+    for path in parser.packages.iter() {
+        packages.store_path(path);
+    }
+    Ok(set)
+}
+
+impl<M> Network<M> {
+
+    /// Load interface definitions from the text into the network, see
+    /// `load` for the syntax. Interfaces are registered with
+    /// `add_interface` and declared packages are stored in the package
+    /// tree of the network. Nothing is changed if the text cannot be
+    /// loaded as a whole.
+    ///
+    /// # Errors
+    /// `BadIdl` is returned with the line and the column of the problem if
+    /// the text is malformed or interfaces cannot be added to the network.
+    pub fn load_idl(&mut self, text: &str) -> Result<(), Error> {
+        let parser = parse(text)?;
+
+        // Check that all interfaces fit before the network is changed.
+        parser.add_interfaces(&mut self.interfaces.clone())?;
+
+        for (key, interface, _, _) in parser.interfaces {
+            self.add_interface(key, interface)?;
+        }
+        for path in parser.packages.iter() {
+            self.packages.store_path(path);
+        }
+        Ok(())
+    }
+}
+
+/// Parse the whole text of the definitions.
+fn parse(text: &str) -> Result<Parser, Error> {
+    let mut parser = Parser::new(tokenize(text)?, text);
+    parser.items(None)?;
+    Ok(parser)
+}
+
+impl Parser {
+
+    fn new(tokens: Vec<Token>, text: &str) -> Self {
+        let line = text.lines().count().max(1);
+        let column = text.lines().last().map_or(0, |l| l.chars().count()) + 1;
+
+        Parser {
+            tokens,
+            pos: 0,
+            end: (line, column),
+            packages: Vec::new(),
+            interfaces: Vec::new(),
+        }
+    }
+
+    /// Add parsed interfaces to the set. Error is reported at the
+    /// interface that cannot be added.
+    fn add_interfaces(&self, set: &mut InterfaceSet) -> Result<(), Error> {
+        for (key, interface, line, column) in self.interfaces.iter() {
+            if let Err(e) = set.add_interface(key.clone(), interface.clone()) {
+                return Err(Error::BadIdl(*line, *column, e.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Error at the position of the current token.
+    fn error<S: Into<String>>(&self, reason: S) -> Error {
+        let (line, column) = match self.tokens.get(self.pos) {
+            Some(token) => (token.line, token.column),
+            None => self.end,
+        };
+        Error::BadIdl(line, column, reason.into())
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|t| t.text.as_str())
+    }
+
+    /// Take the next word. Error is returned if there is no word.
+    fn word(&mut self, what: &str) -> Result<String, Error> {
+        match self.peek().map(str::to_string) {
+            Some(text) if !is_punct(&text) => {
+                self.pos += 1;
+                Ok(text)
+            },
+            _ => Err(self.error(format!("expected {}", what))),
+        }
+    }
+
+    /// Take the expected punctuation.
+    fn expect(&mut self, punct: &str) -> Result<(), Error> {
+        if self.peek() == Some(punct) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", punct)))
+        }
+    }
+
+    /// Parse the value of the next word. Error is reported at the word.
+    fn value<T, F>(&mut self, what: &str, parse: F) -> Result<T, Error>
+            where F: FnOnce(&str) -> Result<T, Error> {
+        let word = self.word(what)?;
+        match parse(&word) {
+            Ok(value) => Ok(value),
+            Err(e) => {
+                self.pos -= 1;
+                Err(self.error(e.to_string()))
+            },
+        }
+    }
+
+    /// Read optional signature of the function that lasts until ';'.
+    /// Error is reported at the start of the signature.
+    fn signature(&mut self) -> Result<Signature, Error> {
+        let start = self.pos;
+        let mut words = Vec::new();
+        while let Some(word) = self.peek() {
+            if is_punct(word) {
+                break;
+            }
+            words.push(word.to_string());
+            self.pos += 1;
+        }
+        if words.is_empty() {
+            return Ok(Signature::default());
+        }
+
+        words.join(" ").parse().map_err(|e: Error| {
+            self.pos = start;
+            self.error(e.to_string())
+        })
+    }
+
+    /// Read packages and interfaces until the end of the package block
+    /// or the end of the text.
+    fn items(&mut self, package: Option<&RcPath>) -> Result<(), Error> {
+        loop {
+            match self.peek() {
+                None if package.is_none() => return Ok(()),
+                Some("}") if package.is_some() => {
+                    self.pos += 1;
+                    return Ok(());
+                },
+                Some("package") => self.package(package)?,
+                Some("interface") => match package {
+                    Some(package) => self.interface(package)?,
+                    None => return Err(self.error(
+                            "interface must be inside of a package")),
+                },
+                None => return Err(self.error("expected '}'")),
+                Some(_) => return Err(self.error(
+                        "expected 'package' or 'interface'")),
+            }
+        }
+    }
+
+    fn package(&mut self, parent: Option<&RcPath>) -> Result<(), Error> {
+        self.pos += 1;
+        let path: RcPath = self.value("package path", str::parse)?;
+
+        // Nested package path is relative to the parent.
+        let path = match parent {
+            Some(parent) => {
+                let mut path_str = Path::to_string(parent);
+                path_str.push('.');
+                path_str.push_str(&Path::to_string(&path));
+                path_str.parse().unwrap()
+            },
+            None => path,
+        };
+        self.packages.push(path.clone());
+
+        match self.peek() {
+            Some(";") => {
+                self.pos += 1;
+                Ok(())
+            },
+            Some("{") => {
+                self.pos += 1;
+                self.items(Some(&path))
+            },
+            _ => Err(self.error("expected ';' or '{'")),
+        }
+    }
+
+    fn interface(&mut self, package: &RcPath) -> Result<(), Error> {
+        let (line, column) = {
+            let token = &self.tokens[self.pos];
+            (token.line, token.column)
+        };
+        self.pos += 1;
+
+        let name = self.value("interface name", |name| {
+            if name.contains('.') {
+                Err(Error::BadPath(name.to_string(),
+                        "interface name must not contain '.'".to_string()))
+            } else {
+                name.parse::<RcPath>()
+            }
+        })?;
+        let path = Path::new_from_parent(package.clone(),
+                name.name().to_string());
+        let version: Version = self.value("version", str::parse)?;
+        self.expect("{")?;
+
+        let mut interface = Interface::new();
+        let mut names = BTreeSet::new();
+        loop {
+            match self.peek() {
+                Some("}") => {
+                    self.pos += 1;
+                    break;
+                },
+                Some("fn") => {
+                    self.pos += 1;
+                    let name = self.word("function name")?;
+                    let version: Version = self.value("version", str::parse)?;
+                    if !names.insert((name.clone(), version)) {
+                        self.pos -= 2;
+                        return Err(self.error(format!(
+                                "function {} {} is already declared",
+                                name, version)));
+                    }
+                    let signature = self.signature()?;
+                    interface.add_fn(InterfaceFunc::with_signature(
+                            name, version, signature));
+                    self.expect(";")?;
+                },
+                Some("requires") => {
+                    self.pos += 1;
+                    let key: InterfaceKey =
+                            self.value("interface key", str::parse)?;
+                    interface.add_prerequisite(key);
+                    self.expect(";")?;
+                },
+                _ => return Err(self.error(
+                        "expected 'fn', 'requires' or '}'")),
+            }
+        }
+
+        let key = InterfaceKey::new(path, version);
+        self.interfaces.push((key, interface, line, column));
+        Ok(())
+    }
+}
+
+/// Whether token is a punctuation.
+fn is_punct(text: &str) -> bool {
+    text == "{" || text == "}" || text == ";"
+}
+
+/// Split the text into words and punctuation, skipping the comments.
+fn tokenize(text: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let mut word: Option<Token> = None;
+        for (j, c) in line.chars().enumerate() {
+            let punct = c == '{' || c == '}' || c == ';';
+            if c.is_whitespace() || punct || c == '#' {
+                if let Some(word) = word.take() {
+                    tokens.push(word);
+                }
+            }
+
+            if c == '#' {
+                break;
+            } else if punct {
+                tokens.push(Token {
+                    text: c.to_string(),
+                    line: i + 1,
+                    column: j + 1,
+                });
+            } else if !c.is_whitespace() {
+                if c.is_control() {
+                    return Err(Error::BadIdl(i + 1, j + 1,
+                            "unexpected control character".to_string()));
+                }
+                word.get_or_insert_with(|| Token {
+                    text: String::new(),
+                    line: i + 1,
+                    column: j + 1,
+                }).text.push(c);
+            }
+        }
+        if let Some(word) = word {
+            tokens.push(word);
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFS: &str = "
+# Streams.
+package root.io;
+
+package root.fs {
+    interface File 1.2.0 {
+        fn open 1.0.0 (path: str, flags: list<u8>) -> chan;
+        fn read 2.0.0;
+        requires root.io.Stream@1.0.0;
+    }
+
+    package local {
+        interface Dir 1.0.0 { fn list 1.0.0; }
+    }
+}
+";
+
+    fn error(text: &str) -> Error {
+        let mut packages = PackageTree::new();
+        load(text, &mut packages).err().unwrap()
+    }
+
+    #[test]
+    fn idl_load() {
+        let mut packages = PackageTree::new();
+        let set = load(DEFS, &mut packages).unwrap();
+
+        let key: InterfaceKey = "root.fs.File@1.2.0".parse().unwrap();
+        let file = set.interface(&key).unwrap();
+        assert_eq!(file.fns().len(), 2);
+        let open = file.fns().iter().find(|f| f.name() == "open").unwrap();
+        assert_eq!(open.signature().to_string(),
+                "(path: str, flags: list<u8>) -> chan");
+        assert!(file.prerequisites()
+                .contains(&"root.io.Stream@1.0.0".parse().unwrap()));
+
+        let key: InterfaceKey = "root.fs.local.Dir@1.0.0".parse().unwrap();
+        assert_eq!(set.interface(&key).unwrap().fns().len(), 1);
+
+        let leaves: Vec<String> = packages.leaves().iter()
+                .map(Path::to_string).collect();
+        assert_eq!(leaves, vec!["root.fs.local", "root.io"]);
+    }
+
+    #[test]
+    fn idl_load_network() {
+        let mut network = Network::new();
+        network.load_idl(DEFS).unwrap();
+
+        let key: InterfaceKey = "root.fs.File@1.2.0".parse().unwrap();
+        assert!(network.interfaces().interface(&key).is_some());
+        assert!(network.packages().entries(key.path()).unwrap()
+                .interfaces().contains(&key));
+        assert!(network.packages().exists(&"root.io".parse().unwrap()));
+
+        // Nothing is added if any of the interfaces is refused.
+        let text = "package b { interface C 1.0.0 {} }\n\
+                package root.fs { interface File 1.2.0 {} }";
+        match network.load_idl(text) {
+            Err(Error::BadIdl(2, 19, _)) => (),
+            e => panic!("unexpected result {:?}", e),
+        }
+        assert!(!network.packages().exists(&"b".parse().unwrap()));
+        assert_eq!(network.interfaces().interfaces().len(), 2);
+    }
+
+    #[test]
+    fn idl_errors() {
+        assert_eq!(error("package a {\n  interface B 1.0 {}\n}"),
+                Error::BadIdl(2, 15, "bad version '1.0': expected three \
+                numbers".to_string()));
+        assert_eq!(error("interface B 1.0.0 {}"), Error::BadIdl(1, 1,
+                "interface must be inside of a package".to_string()));
+        assert_eq!(error("package a {\n"), Error::BadIdl(1, 12,
+                "expected '}'".to_string()));
+        assert_eq!(error("package a..b;"), Error::BadIdl(1, 9,
+                "bad path 'a..b': empty name".to_string()));
+        assert_eq!(error("package a { interface B 1.0.0 { fn f 1.0.0 } }"),
+                Error::BadIdl(1, 44, "expected ';'".to_string()));
+
+        let text = "package a { interface B 1.0.0 { fn f 1.0.0 (x: u1); } }";
+        assert_eq!(error(text), Error::BadIdl(1, 44,
+                "bad signature '(x: u1)': unknown type 'u1'".to_string()));
+
+        let text = "package a {\n interface B 1.0.0 {}\n interface B 1.0.0 {}\n}";
+        match error(text) {
+            Error::BadIdl(3, 2, _) => (),
+            e => panic!("unexpected error {:?}", e),
+        }
+
+        let text = "package a { interface B 1.0.0 { fn f 1.0.0; fn f 1.0.0; } }";
+        assert_eq!(error(text), Error::BadIdl(1, 48,
+                "function f 1.0.0 is already declared".to_string()));
+    }
+}
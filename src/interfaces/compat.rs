@@ -0,0 +1,313 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{Func, Interface, Key, Version};
+use crate::Error;
+
+/// Kind of the change between two versions of the interface or the
+/// kind of the version bump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Change {
+
+    /// Nothing changed that would affect users of the interface.
+    Patch,
+
+    /// Functions were added. Existing users are not affected.
+    Minor,
+
+    /// Functions were removed or their versions or signatures were
+    /// changed. Existing users may be broken.
+    Major,
+}
+
+/// Result of the comparison of two versions of the interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Compatibility {
+    change: Change,
+    bump: Option<Change>,
+
+    added: BTreeSet<Func>,
+    removed: BTreeSet<Func>,
+    changed: BTreeSet<(Func, Func)>,
+
+    prerequisites_added: BTreeSet<Key>,
+    prerequisites_removed: BTreeSet<Key>,
+}
+
+impl Compatibility {
+
+    /// Compare old and new versions of the interface that are located
+    /// at the same path.
+    ///
+    /// # Errors
+    /// `IncomparableInterfaces` is returned if keys have different paths
+    /// or same versions.
+    pub fn check(old_key: &Key, old: &Interface, new_key: &Key,
+            new: &Interface) -> Result<Self, Error> {
+        if old_key.path() != new_key.path()
+                || old_key.version() == new_key.version() {
+            return Err(Error::IncomparableInterfaces(
+                    old_key.clone(), new_key.clone()));
+        }
+
+        // Functions are matched by their names.
+        let by_name = |fns: &BTreeSet<Func>| {
+            let mut map: BTreeMap<String, BTreeSet<Func>> = BTreeMap::new();
+            for func in fns {
+                map.entry(func.name().to_string()).or_default()
+                        .insert(func.clone());
+            }
+            map
+        };
+        let old_fns = by_name(old.fns());
+        let new_fns = by_name(new.fns());
+
+        let mut added = BTreeSet::new();
+        let mut removed = BTreeSet::new();
+        let mut changed = BTreeSet::new();
+        for (name, old_versions) in old_fns.iter() {
+            let new_versions = match new_fns.get(name) {
+                Some(new_versions) => new_versions,
+                None => {
+                    removed.extend(old_versions.iter().cloned());
+                    continue;
+                },
+            };
+
+            // Function is changed if it keeps its version or signature.
+            // Functions that have nothing in common are removed and added.
+            let mut came: Vec<&Func> = new_versions.difference(old_versions)
+                    .collect();
+            for old in old_versions.difference(new_versions) {
+                let same_version = came.iter()
+                        .position(|new| new.version() == old.version());
+                let pair = same_version.or_else(|| came.iter()
+                        .position(|new| new.signature() == old.signature()));
+                match pair {
+                    Some(i) => {
+                        let new = came.remove(i);
+                        changed.insert((old.clone(), new.clone()))
+                    },
+                    None => removed.insert(old.clone()),
+                };
+            }
+            added.extend(came.into_iter().cloned());
+        }
+        for (name, new_versions) in new_fns.iter() {
+            if !old_fns.contains_key(name) {
+                added.extend(new_versions.iter().cloned());
+            }
+        }
+
+        let change = if !removed.is_empty() || !changed.is_empty() {
+            Change::Major
+        } else if !added.is_empty() {
+            Change::Minor
+        } else {
+            Change::Patch
+        };
+
+        Ok(Compatibility {
+            change,
+            bump: bump(old_key.version(), new_key.version()),
+            added,
+            removed,
+            changed,
+            prerequisites_added: new.prerequisites()
+                    .difference(old.prerequisites()).cloned().collect(),
+            prerequisites_removed: old.prerequisites()
+                    .difference(new.prerequisites()).cloned().collect(),
+        })
+    }
+
+    /// Kind of the change of the interface.
+    pub fn change(&self) -> Change {
+        self.change
+    }
+
+    /// Kind of the version bump from old key to the new one. None if
+    /// new version is lower than the old one.
+    pub fn bump(&self) -> Option<Change> {
+        self.bump
+    }
+
+    /// Whether version bump is big enough for the change of the interface.
+    pub fn is_consistent(&self) -> bool {
+        match self.bump {
+            Some(bump) => bump >= self.change,
+            None => false,
+        }
+    }
+
+    /// Functions that appeared in the new version.
+    pub fn added(&self) -> &BTreeSet<Func> {
+        &self.added
+    }
+
+    /// Functions that are not present in the new version.
+    pub fn removed(&self) -> &BTreeSet<Func> {
+        &self.removed
+    }
+
+    /// Functions which version or signature was changed. Each pair
+    /// contains old and new function.
+    pub fn changed(&self) -> &BTreeSet<(Func, Func)> {
+        &self.changed
+    }
+
+    /// Whether prerequisites of the interface were changed. Implementers
+    /// may need to implement other interfaces then.
+    pub fn prerequisites_changed(&self) -> bool {
+        !self.prerequisites_added.is_empty()
+                || !self.prerequisites_removed.is_empty()
+    }
+
+    /// Prerequisites that appeared in the new version.
+    pub fn prerequisites_added(&self) -> &BTreeSet<Key> {
+        &self.prerequisites_added
+    }
+
+    /// Prerequisites that are not present in the new version.
+    pub fn prerequisites_removed(&self) -> &BTreeSet<Key> {
+        &self.prerequisites_removed
+    }
+}
+
+/// Kind of the version bump. Before 1.0.0 the minor part is bumped for
+/// breaking changes and before 0.1.0 any bump is breaking.
+fn bump(old: &Version, new: &Version) -> Option<Change> {
+    if new <= old {
+        None
+    } else if new.major > old.major {
+        Some(Change::Major)
+    } else if new.minor > old.minor {
+        if old.major == 0 {
+            Some(Change::Major)
+        } else {
+            Some(Change::Minor)
+        }
+    } else if old.major == 0 && old.minor == 0 {
+        Some(Change::Major)
+    } else {
+        Some(Change::Patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interface(fns: &[(&str, u32)], prerequisites: &[&str]) -> Interface {
+        let mut i = Interface::new();
+        for (name, major) in fns {
+            i.add_fn(Func::new(name.to_string(), Version::new(*major, 0, 0)));
+        }
+        for p in prerequisites {
+            i.add_prerequisite(p.parse().unwrap());
+        }
+        i
+    }
+
+    fn key(s: &str) -> Key {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn compat_minor() {
+        let old = interface(&[("open", 1)], &[]);
+        let new = interface(&[("open", 1), ("read", 1)], &[]);
+
+        let c = Compatibility::check(&key("a.b@1.2.0"), &old,
+                &key("a.b@1.3.0"), &new).unwrap();
+        assert_eq!(c.change(), Change::Minor);
+        assert_eq!(c.bump(), Some(Change::Minor));
+        assert!(c.is_consistent());
+        assert_eq!(c.added().len(), 1);
+        assert!(!c.prerequisites_changed());
+
+        let c = Compatibility::check(&key("a.b@1.2.0"), &old,
+                &key("a.b@1.2.1"), &new).unwrap();
+        assert!(!c.is_consistent());
+    }
+
+    #[test]
+    fn compat_major() {
+        let old = interface(&[("open", 1), ("read", 1)], &["a.c@1.0.0"]);
+        let new = interface(&[("open", 2)], &["a.d@1.0.0"]);
+
+        let c = Compatibility::check(&key("a.b@1.2.0"), &old,
+                &key("a.b@1.3.0"), &new).unwrap();
+        assert_eq!(c.change(), Change::Major);
+        assert!(!c.is_consistent());
+        assert_eq!(c.removed().len(), 1);
+        assert_eq!(c.changed().iter().next().unwrap().1.version(),
+                &Version::new(2, 0, 0));
+        assert!(c.prerequisites_changed());
+        assert!(c.prerequisites_added().contains(&key("a.d@1.0.0")));
+        assert!(c.prerequisites_removed().contains(&key("a.c@1.0.0")));
+
+        let c = Compatibility::check(&key("a.b@1.2.0"), &old,
+                &key("a.b@2.0.0"), &new).unwrap();
+        assert!(c.is_consistent());
+
+        // Minor bump is breaking before 1.0.0.
+        let c = Compatibility::check(&key("a.b@0.2.0"), &old,
+                &key("a.b@0.3.0"), &new).unwrap();
+        assert!(c.is_consistent());
+    }
+
+    #[test]
+    fn compat_signature() {
+        let func = |signature: &str| {
+            Func::with_signature("read".to_string(), Version::new(1, 0, 0),
+                    signature.parse().unwrap())
+        };
+        let mut old = Interface::new();
+        old.add_fn(func("(len: u32) -> bytes"));
+        let mut new = Interface::new();
+        new.add_fn(func("(len: u64) -> bytes"));
+
+        let c = Compatibility::check(&key("a.b@1.2.0"), &old,
+                &key("a.b@1.3.0"), &new).unwrap();
+        assert_eq!(c.change(), Change::Major);
+        assert_eq!(c.changed().len(), 1);
+
+        // Functions are paired by the version first and by the signature
+        // then. Others are reported as removed and added.
+        let func = |major, signature: &str| {
+            Func::with_signature("read".to_string(), Version::new(major, 0, 0),
+                    signature.parse().unwrap())
+        };
+        let mut old = Interface::new();
+        old.add_fn(func(1, "()"));
+        old.add_fn(func(2, "(len: u32)"));
+        old.add_fn(func(3, "(len: u32) -> bytes"));
+        let mut new = Interface::new();
+        new.add_fn(func(2, "(len: u64)"));
+        new.add_fn(func(4, "()"));
+        new.add_fn(func(5, "(buf: bytes)"));
+
+        let c = Compatibility::check(&key("a.b@1.2.0"), &old,
+                &key("a.b@2.0.0"), &new).unwrap();
+        assert!(c.changed().contains(&(func(2, "(len: u32)"),
+                func(2, "(len: u64)"))));
+        assert!(c.changed().contains(&(func(1, "()"), func(4, "()"))));
+        assert_eq!(c.changed().len(), 2);
+        assert!(c.removed().contains(&func(3, "(len: u32) -> bytes")));
+        assert!(c.added().contains(&func(5, "(buf: bytes)")));
+    }
+
+    #[test]
+    fn compat_errors() {
+        let i = Interface::new();
+        assert!(Compatibility::check(&key("a.b@1.0.0"), &i,
+                &key("a.c@1.1.0"), &i).is_err());
+        assert!(Compatibility::check(&key("a.b@1.0.0"), &i,
+                &key("a.b@1.0.0"), &i).is_err());
+
+        let c = Compatibility::check(&key("a.b@1.1.0"), &i,
+                &key("a.b@1.0.0"), &i).unwrap();
+        assert_eq!(c.change(), Change::Patch);
+        assert_eq!(c.bump(), None);
+        assert!(!c.is_consistent());
+    }
+}
@@ -1,8 +1,23 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::rc::Rc;
 use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
 
 use super::path::*;
+use crate::Error;
+
+/// Requirements for interface versions.
+mod req;
+pub use self::req::VersionReq;
+
+/// Types and signatures of the functions.
+mod signature;
+pub use self::signature::{Signature, Type};
+
+/// Compatibility between versions of the interface.
+mod compat;
+pub use self::compat::{Change, Compatibility};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Version {
@@ -19,7 +34,7 @@ pub struct Key {
 }
 
 /// Information about interface.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Interface {
     fns: BTreeSet<Func>,
 
@@ -39,10 +54,13 @@ pub struct Func {
     /// Each function is uniquely identified by version. Even there can be
     /// several functions with same name and different versions.
     version: Version,
+
+    /// Parameters and return type of the function.
+    signature: Signature,
 }
 
 /// Set of all interfaces and their relations.
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct InterfaceSet {
     map: BTreeMap<Key, Rc<Interface>>,
 }
@@ -66,21 +84,42 @@ impl Key {
     }
 }
 
-impl ToString for Version {
+impl fmt::Display for Version {
 
-    fn to_string(&self) -> String {
-        let maj = self.major.to_string();
-        let min = self.minor.to_string();
-        let ptc = self.patch.to_string();
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
 
-        let mut s = String::with_capacity(maj.len() + min.len()
-                + ptc.len() + 2);
-        s.push_str(&maj);
-        s.push('.');
-        s.push_str(&min);
-        s.push('.');
-        s.push_str(&ptc);
-        s
+impl FromStr for Version {
+    type Err = Error;
+
+    /// Parse version of the form `1.7.2`.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let error = |reason: &str| {
+            Error::BadVersion(s.to_string(), reason.to_string())
+        };
+
+        let mut numbers = [0; 3];
+        let mut parts = s.split('.');
+        for number in numbers.iter_mut() {
+            let part = match parts.next() {
+                Some(part) => part,
+                None => return Err(error("expected three numbers")),
+            };
+            if part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+                return Err(error("bad number"));
+            }
+            *number = match part.parse() {
+                Ok(n) => n,
+                Err(_) => return Err(error("number is too big")),
+            };
+        }
+        if parts.next().is_some() {
+            return Err(error("expected three numbers"));
+        }
+
+        Ok(Version::new(numbers[0], numbers[1], numbers[2]))
     }
 }
 
@@ -140,8 +179,11 @@ impl Ord for Func {
         let cmp = self.name.cmp(&other.name).reverse();
 
         if cmp == Equal {
-            // Names are equal, check versions.
-            self.version.cmp(&other.version)
+            // Names are equal, check versions and then signatures.
+            match self.version.cmp(&other.version) {
+                Equal => self.signature.cmp(&other.signature),
+                cmp => cmp,
+            }
         } else {
             cmp
         }
@@ -150,14 +192,26 @@ impl Ord for Func {
 
 impl Func {
 
-    /// Create new function entry.
+    /// Create new function entry without parameters and return value.
     pub fn new(name: String, version: Version) -> Func {
+        Self::with_signature(name, version, Default::default())
+    }
+
+    /// Create new function entry with given signature.
+    pub fn with_signature(name: String, version: Version,
+            signature: Signature) -> Func {
         Func {
             name,
             version,
+            signature,
         }
     }
 
+    /// Function signature.
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
     /// Function version.
     pub fn version(&self) -> &Version {
         &self.version
@@ -199,13 +253,44 @@ impl Interface {
     }
 }
 
-impl Default for Interface {
+/// Take the interface out of Rc. It is cloned if someone else still
+/// holds it.
+fn unwrap_interface(rc: Rc<Interface>) -> Interface {
+    Rc::try_unwrap(rc).unwrap_or_else(|rc| (*rc).clone())
+}
+
+impl FromStr for Key {
+    type Err = Error;
+
+    /// Parse interface key of the form `root.foo.Bar@1.7.2`.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let error = |reason: String| {
+            Error::BadInterfaceKey(s.to_string(), reason)
+        };
+
+        let at = match s.rfind('@') {
+            Some(at) => at,
+            None => return Err(error("expected '@' before version".into())),
+        };
+
+        let path = match s[..at].parse() {
+            Ok(path) => path,
+            Err(Error::BadPath(_, reason)) => return Err(error(reason)),
+            Err(e) => return Err(e),
+        };
+        let version = match s[at + 1..].parse() {
+            Ok(version) => version,
+            Err(Error::BadVersion(_, reason)) => return Err(error(reason)),
+            Err(e) => return Err(e),
+        };
+        Ok(Key::new(path, version))
+    }
+}
+
+impl fmt::Display for Key {
 
-    fn default() -> Self {
-        Interface {
-            fns: Default::default(),
-            prerequisites: Default::default(),
-        }
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}@{}", Path::to_string(&self.path), self.version)
     }
 }
 
@@ -245,11 +330,23 @@ impl InterfaceSet {
     }
 
     /// Add new interface to the map. If there is present interface with
-    /// same key, new interface will be discarded and Err returned.
+    /// same key, new interface will be discarded and `InterfaceExists`
+    /// error returned. `PrerequisiteCycle` error is returned if interface
+    /// would depend on itself through it's prerequisites.
+    ///
+    /// Prerequisites do not need to be in the set yet.
     pub fn add_interface(&mut self, key: Key, interface: Interface)
-            -> Result<(), ()> {
+            -> Result<(), Error> {
         if self.map.contains_key(&key) {
-            return Err(());
+            return Err(Error::InterfaceExists(key));
+        }
+
+        for prerequisite in interface.prerequisites() {
+            if let Some(chain) = self.prerequisite_chain(prerequisite, &key) {
+                let mut cycle = vec![key];
+                cycle.extend(chain);
+                return Err(Error::PrerequisiteCycle(cycle));
+            }
         }
 
         let rc = Rc::new(interface);
@@ -257,24 +354,187 @@ impl InterfaceSet {
         Ok(())
     }
 
-    /// Remove interface from the map. If there is no such key Err is returned.
+    /// Remove interface from the map. Interface is not removed if other
+    /// interfaces require it.
+    ///
+    /// # Errors
+    /// `UnknownInterface` is returned if there is no such key.
+    /// `InterfaceInUse` is returned with the interfaces that list given
+    /// one as their prerequisite.
     pub fn remove_interface(&mut self, key: &Key)
-            -> Result<Interface, ()> {
-        let i = self.map.remove(key);
-        if i.is_none() {
-            Err(())
-        } else {
-            Ok(Rc::try_unwrap(i.unwrap()).unwrap())
+            -> Result<Interface, Error> {
+        if !self.map.contains_key(key) {
+            return Err(Error::UnknownInterface(key.clone()));
+        }
+
+        let dependents = self.dependents(key);
+        if !dependents.is_empty() {
+            return Err(Error::InterfaceInUse(key.clone(), dependents,
+                    BTreeSet::new()));
+        }
+        Ok(unwrap_interface(self.map.remove(key).unwrap()))
+    }
+
+    /// Remove interface together with all interfaces that require it
+    /// directly or through other prerequisites.
+    ///
+    /// # Returns
+    /// All removed interfaces.
+    ///
+    /// # Errors
+    /// `UnknownInterface` is returned if there is no such key.
+    pub fn remove_cascade(&mut self, key: &Key)
+            -> Result<BTreeMap<Key, Interface>, Error> {
+        if !self.map.contains_key(key) {
+            return Err(Error::UnknownInterface(key.clone()));
+        }
+
+        let mut keys = BTreeSet::new();
+        let mut stack = vec![key.clone()];
+        while let Some(key) = stack.pop() {
+            if keys.insert(key.clone()) {
+                stack.extend(self.dependents(&key));
+            }
         }
+
+        Ok(keys.into_iter().map(|key| {
+            let interface = unwrap_interface(self.map.remove(&key).unwrap());
+            (key, interface)
+        }).collect())
+    }
+
+    /// Interfaces that list given one as their prerequisite.
+    pub fn dependents(&self, key: &Key) -> BTreeSet<Key> {
+        self.map.iter()
+                .filter(|(_, interface)| interface.prerequisites().contains(key))
+                .map(|(key, _)| key.clone())
+                .collect()
     }
 
     pub fn interface(&self, key: &Key) -> Option<Rc<Interface>> {
-        let i = self.map.get(key);
-        match i {
-            Some(t) => Some(t.clone()),
-            None    => None,
+        self.map.get(key).cloned()
+    }
+
+    /// All interfaces that must be implemented in order to implement
+    /// given one: it's prerequisites, their prerequisites and so on.
+    ///
+    /// # Errors
+    /// `UnknownInterface` is returned if interface or any of it's
+    /// prerequisites is not in the set.
+    pub fn prerequisite_closure(&self, key: &Key)
+            -> Result<BTreeSet<Key>, Error> {
+        if !self.map.contains_key(key) {
+            return Err(Error::UnknownInterface(key.clone()));
+        }
+
+        let (closure, unknown) = self.walk_prerequisites(Some(key));
+        match unknown.into_iter().next() {
+            Some(unknown) => Err(Error::UnknownInterface(unknown)),
+            None => Ok(closure),
         }
     }
+
+    /// Collect prerequisites of given interfaces transitively. Returns
+    /// the prerequisites that were found in the set and the ones that
+    /// were not.
+    pub(crate) fn walk_prerequisites<'a, I>(&self, keys: I)
+            -> (BTreeSet<Key>, BTreeSet<Key>)
+            where I: IntoIterator<Item = &'a Key> {
+        let mut found = BTreeSet::new();
+        let mut unknown = BTreeSet::new();
+        let mut stack: Vec<Key> = keys.into_iter().cloned().collect();
+        let mut visited = BTreeSet::new();
+
+        while let Some(key) = stack.pop() {
+            if !visited.insert(key.clone()) {
+                continue;
+            }
+            let interface = match self.map.get(&key) {
+                Some(interface) => interface,
+                None => {
+                    unknown.insert(key);
+                    continue;
+                },
+            };
+            for prerequisite in interface.prerequisites() {
+                found.insert(prerequisite.clone());
+                stack.push(prerequisite.clone());
+            }
+        }
+
+        found.retain(|key| !unknown.contains(key));
+        (found, unknown)
+    }
+
+    /// Chain of prerequisites that leads from one interface to another.
+    /// Chain starts with `from` and ends with the interface that has `to`
+    /// as prerequisite.
+    fn prerequisite_chain(&self, from: &Key, to: &Key) -> Option<Vec<Key>> {
+        if from == to {
+            return Some(vec![]);
+        }
+
+        let mut parents: BTreeMap<Key, Option<Key>> = BTreeMap::new();
+        let mut queue = VecDeque::new();
+        parents.insert(from.clone(), None);
+        queue.push_back(from.clone());
+
+        while let Some(key) = queue.pop_front() {
+            let interface = match self.map.get(&key) {
+                Some(interface) => interface,
+                None => continue,
+            };
+
+            if interface.prerequisites().contains(to) {
+                // Restore the chain by going back to the start.
+                let mut chain = vec![key.clone()];
+                let mut cur = parents[&key].clone();
+                while let Some(parent) = cur {
+                    cur = parents[&parent].clone();
+                    chain.push(parent);
+                }
+                chain.reverse();
+                return Some(chain);
+            }
+
+            for prerequisite in interface.prerequisites() {
+                if !parents.contains_key(prerequisite) {
+                    parents.insert(prerequisite.clone(), Some(key.clone()));
+                    queue.push_back(prerequisite.clone());
+                }
+            }
+        }
+        None
+    }
+
+    /// Compare two versions of the interface that are stored in the set.
+    ///
+    /// # Errors
+    /// `UnknownInterface` is returned if any of the interfaces was not
+    /// found. Errors of `Compatibility::check` are returned if interfaces
+    /// cannot be compared.
+    pub fn compatibility(&self, old: &Key, new: &Key)
+            -> Result<Compatibility, Error> {
+        let old_interface = match self.map.get(old) {
+            Some(interface) => interface,
+            None => return Err(Error::UnknownInterface(old.clone())),
+        };
+        let new_interface = match self.map.get(new) {
+            Some(interface) => interface,
+            None => return Err(Error::UnknownInterface(new.clone())),
+        };
+        Compatibility::check(old, old_interface, new, new_interface)
+    }
+
+    /// Find the interface with given path and the highest version that
+    /// satisfies the requirement. None is returned if no such interface
+    /// was found.
+    pub fn resolve(&self, path: &RcPath, req: &VersionReq) -> Option<Key> {
+        self.map.keys()
+                .filter(|key| key.path() == path && req.matches(key.version()))
+                .max_by(|a, b| a.version().cmp(b.version()))
+                .cloned()
+    }
 }
 
 #[cfg(test)]
@@ -288,6 +548,30 @@ mod tests {
         assert_eq!("1.7.2", s);
     }
 
+    #[test]
+    fn version_parse() {
+        assert_eq!("1.7.2".parse(), Ok(Version::new(1, 7, 2)));
+        assert_eq!("1.7".parse::<Version>(), Err(Error::BadVersion(
+                "1.7".to_string(), "expected three numbers".to_string())));
+        assert!("1.7.2.0".parse::<Version>().is_err());
+        assert!("1..2".parse::<Version>().is_err());
+        assert!("1.+7.2".parse::<Version>().is_err());
+        assert!("1.7.99999999999".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn key_parse() {
+        let key: Key = "root.foo.Bar@1.7.2".parse().unwrap();
+        assert_eq!(Path::to_string(key.path()), "root.foo.Bar");
+        assert_eq!(*key.version(), Version::new(1, 7, 2));
+        assert_eq!(key.to_string(), "root.foo.Bar@1.7.2");
+
+        assert!("root.foo.Bar".parse::<Key>().is_err());
+        assert!("root..Bar@1.7.2".parse::<Key>().is_err());
+        assert_eq!("a@1.x.2".parse::<Key>(), Err(Error::BadInterfaceKey(
+                "a@1.x.2".to_string(), "bad number".to_string())));
+    }
+
     #[test]
     fn version_major_cmp() {
         let ver1 = Version::new(1, 7, 2);
@@ -343,4 +627,105 @@ mod tests {
 
         assert!(f1 > f2);
     }
+
+    #[test]
+    fn func_signature_cmp() {
+        let f1 = Func::new("a".to_string(), Version::new(1, 0, 0));
+        let f2 = Func::with_signature("a".to_string(), Version::new(1, 0, 0),
+                "(x: u32)".parse().unwrap());
+
+        assert!(f1 != f2);
+        assert!(f1 < f2);
+    }
+
+    #[test]
+    fn interface_resolve() {
+        let p0 = Path::new("a".to_string());
+        let p0 = Path::new_from_parent(p0, "b".to_string());
+        let p1 = Path::new("c".to_string());
+
+        let mut is = InterfaceSet::new();
+        for (path, major, minor) in &[(&p0, 1, 0), (&p0, 1, 4), (&p0, 2, 0),
+                (&p1, 1, 9)] {
+            let key = Key::new((*path).clone(), Version::new(*major, *minor, 0));
+            is.add_interface(key, Interface::new()).unwrap();
+        }
+
+        let key = is.resolve(&p0, &"^1.0".parse().unwrap()).unwrap();
+        assert_eq!(*key.version(), Version::new(1, 4, 0));
+        let key = is.resolve(&p0, &VersionReq::any()).unwrap();
+        assert_eq!(*key.version(), Version::new(2, 0, 0));
+        assert!(is.resolve(&p0, &"^3".parse().unwrap()).is_none());
+        assert!(is.resolve(&Path::new("b".to_string()), &VersionReq::any())
+                .is_none());
+    }
+
+    #[test]
+    fn interface_prerequisites() {
+        let k = |s: &str| -> Key { s.parse().unwrap() };
+        let with = |prerequisites: &[&str]| {
+            let mut i = Interface::new();
+            for p in prerequisites {
+                i.add_prerequisite(k(p));
+            }
+            i
+        };
+
+        let mut is = InterfaceSet::new();
+        is.add_interface(k("a@1.0.0"), with(&["b@1.0.0"])).unwrap();
+        is.add_interface(k("b@1.0.0"), with(&["c@1.0.0", "d@1.0.0"])).unwrap();
+        is.add_interface(k("c@1.0.0"), with(&["d@1.0.0"])).unwrap();
+
+        assert_eq!(is.prerequisite_closure(&k("a@1.0.0")),
+                Err(Error::UnknownInterface(k("d@1.0.0"))));
+        assert_eq!(is.prerequisite_closure(&k("x@1.0.0")),
+                Err(Error::UnknownInterface(k("x@1.0.0"))));
+
+        // Cycles are refused.
+        assert_eq!(is.add_interface(k("d@1.0.0"), with(&["a@1.0.0"])),
+                Err(Error::PrerequisiteCycle(vec![k("d@1.0.0"), k("a@1.0.0"),
+                        k("b@1.0.0")])));
+        assert_eq!(is.add_interface(k("d@2.0.0"), with(&["d@2.0.0"])),
+                Err(Error::PrerequisiteCycle(vec![k("d@2.0.0")])));
+
+        is.add_interface(k("d@1.0.0"), Interface::new()).unwrap();
+        let closure = is.prerequisite_closure(&k("a@1.0.0")).unwrap();
+        assert_eq!(closure.len(), 3);
+        assert!(closure.contains(&k("d@1.0.0")));
+    }
+
+    #[test]
+    fn interface_remove() {
+        let k = |s: &str| -> Key { s.parse().unwrap() };
+        let mut is = InterfaceSet::new();
+        let mut a = Interface::new();
+        a.add_prerequisite(k("b@1.0.0"));
+        is.add_interface(k("a@1.0.0"), a).unwrap();
+        let mut b = Interface::new();
+        b.add_prerequisite(k("c@1.0.0"));
+        is.add_interface(k("b@1.0.0"), b).unwrap();
+        is.add_interface(k("c@1.0.0"), Interface::new()).unwrap();
+        let mut d = Interface::new();
+        d.add_fn(Func::new("f".to_string(), Version::new(1, 0, 0)));
+        is.add_interface(k("d@1.0.0"), d).unwrap();
+
+        assert_eq!(is.remove_interface(&k("c@1.0.0")).err(),
+                Some(Error::InterfaceInUse(k("c@1.0.0"),
+                        vec![k("b@1.0.0")].into_iter().collect(),
+                        BTreeSet::new())));
+        assert_eq!(is.remove_interface(&k("x@1.0.0")).err(),
+                Some(Error::UnknownInterface(k("x@1.0.0"))));
+
+        // Interface that is still referenced is cloned out and the holder
+        // keeps it's copy.
+        let held = is.interface(&k("d@1.0.0")).unwrap();
+        let removed = is.remove_interface(&k("d@1.0.0")).unwrap();
+        assert_eq!(removed.fns().len(), 1);
+        assert_eq!(removed.fns(), held.fns());
+        assert!(is.interface(&k("d@1.0.0")).is_none());
+
+        let removed = is.remove_cascade(&k("c@1.0.0")).unwrap();
+        assert_eq!(removed.len(), 3);
+        assert!(is.interfaces().is_empty());
+    }
 }
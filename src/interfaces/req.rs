@@ -0,0 +1,318 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::Version;
+use crate::Error;
+
+/// Requirement that versions must satisfy. It consists of comparators
+/// separated by commas and version matches it when all comparators match.
+///
+/// Supported comparators are:
+/// * `1.2.3` or `=1.2.3` - exact version. Missing parts match any
+///   number, so `=1.2` matches `1.2.0` and `1.2.7`;
+/// * `^1.2.3` - compatible version, the leftmost non-zero part must not
+///   change: `>=1.2.3, <2.0.0`;
+/// * `~1.2.3` - only patch may change: `>=1.2.3, <1.3.0`. If only major
+///   is given then minor may change too;
+/// * `>1.2`, `>=1.2`, `<1.2`, `<=1.2` - comparison;
+/// * `*` - any version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionReq {
+    comparators: Vec<Comparator>,
+}
+
+/// Single comparator of the requirement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Comparator {
+    op: Op,
+    major: u32,
+    minor: Option<u32>,
+    patch: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    Tilde,
+    Caret,
+    Any,
+}
+
+impl VersionReq {
+
+    /// Requirement that matches any version.
+    pub fn any() -> Self {
+        VersionReq {
+            comparators: vec![Comparator {
+                op: Op::Any,
+                major: 0,
+                minor: None,
+                patch: None,
+            }],
+        }
+    }
+
+    /// Requirement that matches only given version.
+    pub fn exact(version: &Version) -> Self {
+        VersionReq {
+            comparators: vec![Comparator {
+                op: Op::Exact,
+                major: version.major,
+                minor: Some(version.minor),
+                patch: Some(version.patch),
+            }],
+        }
+    }
+
+    /// Check whether version satisfies the requirement.
+    pub fn matches(&self, version: &Version) -> bool {
+        self.comparators.iter().all(|c| c.matches(version))
+    }
+}
+
+impl Comparator {
+
+    /// Lowest version that satisfies the comparator.
+    fn lower(&self) -> Version {
+        Version::new(self.major,
+                self.minor.unwrap_or(0), self.patch.unwrap_or(0))
+    }
+
+    /// Lowest version that is above all versions which are matched by
+    /// the version given in comparator. For `1.2` it is `1.3.0`.
+    fn above(&self) -> Version {
+        match (self.minor, self.patch) {
+            (None, _) => Version::new(self.major.saturating_add(1), 0, 0),
+            (Some(minor), None) =>
+                Version::new(self.major, minor.saturating_add(1), 0),
+            (Some(minor), Some(patch)) =>
+                Version::new(self.major, minor, patch.saturating_add(1)),
+        }
+    }
+
+    fn matches(&self, version: &Version) -> bool {
+        use self::Op::*;
+
+        let lower = self.lower();
+        match self.op {
+            Exact => *version >= lower && *version < self.above(),
+            Greater => *version >= self.above(),
+            GreaterEq => *version >= lower,
+            Less => *version < lower,
+            LessEq => *version < self.above(),
+            Tilde => {
+                let upper = match self.minor {
+                    Some(minor) =>
+                        Version::new(self.major, minor.saturating_add(1), 0),
+                    None => Version::new(self.major.saturating_add(1), 0, 0),
+                };
+                *version >= lower && *version < upper
+            },
+            Caret => {
+                // The leftmost non-zero part that is given must not change.
+                let upper = match (self.minor, self.patch) {
+                    (Some(minor), Some(patch))
+                            if self.major == 0 && minor == 0 =>
+                        Version::new(0, 0, patch.saturating_add(1)),
+                    (Some(minor), _) if self.major == 0 =>
+                        Version::new(0, minor.saturating_add(1), 0),
+                    _ => Version::new(self.major.saturating_add(1), 0, 0),
+                };
+                *version >= lower && *version < upper
+            },
+            Any => true,
+        }
+    }
+}
+
+impl FromStr for VersionReq {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let error = |reason: &str| {
+            Error::BadVersionReq(s.to_string(), reason.to_string())
+        };
+
+        let mut comparators = Vec::new();
+        for part in s.split(',') {
+            let part = part.trim();
+            if part.is_empty() {
+                return Err(error("empty comparator"));
+            }
+            if part == "*" {
+                comparators.push(Comparator {
+                    op: Op::Any,
+                    major: 0,
+                    minor: None,
+                    patch: None,
+                });
+                continue;
+            }
+
+            let (op, rest) = if let Some(rest) = part.strip_prefix(">=") {
+                (Op::GreaterEq, rest)
+            } else if let Some(rest) = part.strip_prefix("<=") {
+                (Op::LessEq, rest)
+            } else if let Some(rest) = part.strip_prefix('>') {
+                (Op::Greater, rest)
+            } else if let Some(rest) = part.strip_prefix('<') {
+                (Op::Less, rest)
+            } else if let Some(rest) = part.strip_prefix('=') {
+                (Op::Exact, rest)
+            } else if let Some(rest) = part.strip_prefix('~') {
+                (Op::Tilde, rest)
+            } else if let Some(rest) = part.strip_prefix('^') {
+                (Op::Caret, rest)
+            } else {
+                (Op::Exact, part)
+            };
+
+            let mut numbers = Vec::new();
+            for number in rest.trim().split('.') {
+                if !number.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(error("bad version number"));
+                }
+                match number.parse() {
+                    Ok(number) => numbers.push(number),
+                    Err(_) => return Err(error("bad version number")),
+                }
+            }
+            if numbers.len() > 3 {
+                return Err(error("too many version numbers"));
+            }
+
+            comparators.push(Comparator {
+                op,
+                major: numbers[0],
+                minor: numbers.get(1).cloned(),
+                patch: numbers.get(2).cloned(),
+            });
+        }
+
+        Ok(VersionReq {
+            comparators,
+        })
+    }
+}
+
+impl fmt::Display for VersionReq {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, comparator) in self.comparators.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", comparator)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Comparator {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Op::*;
+
+        let op = match self.op {
+            Exact => "=",
+            Greater => ">",
+            GreaterEq => ">=",
+            Less => "<",
+            LessEq => "<=",
+            Tilde => "~",
+            Caret => "^",
+            Any => return write!(f, "*"),
+        };
+        write!(f, "{}{}", op, self.major)?;
+        if let Some(minor) = self.minor {
+            write!(f, ".{}", minor)?;
+        }
+        if let Some(patch) = self.patch {
+            write!(f, ".{}", patch)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn req(s: &str) -> VersionReq {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn version_req_caret() {
+        let r = req("^1.2");
+        assert!(r.matches(&Version::new(1, 2, 0)));
+        assert!(r.matches(&Version::new(1, 9, 3)));
+        assert!(!r.matches(&Version::new(1, 1, 9)));
+        assert!(!r.matches(&Version::new(2, 0, 0)));
+
+        let r = req("^0.2.3");
+        assert!(r.matches(&Version::new(0, 2, 5)));
+        assert!(!r.matches(&Version::new(0, 3, 0)));
+
+        let r = req("^0.0.3");
+        assert!(r.matches(&Version::new(0, 0, 3)));
+        assert!(!r.matches(&Version::new(0, 0, 4)));
+    }
+
+    #[test]
+    fn version_req_tilde() {
+        let r = req("~1.2.3");
+        assert!(r.matches(&Version::new(1, 2, 3)));
+        assert!(r.matches(&Version::new(1, 2, 9)));
+        assert!(!r.matches(&Version::new(1, 2, 2)));
+        assert!(!r.matches(&Version::new(1, 3, 0)));
+
+        let r = req("~1");
+        assert!(r.matches(&Version::new(1, 5, 0)));
+        assert!(!r.matches(&Version::new(2, 0, 0)));
+    }
+
+    #[test]
+    fn version_req_range() {
+        let r = req(">=1.0, <2.0");
+        assert!(r.matches(&Version::new(1, 0, 0)));
+        assert!(r.matches(&Version::new(1, 99, 1)));
+        assert!(!r.matches(&Version::new(2, 0, 0)));
+        assert!(!r.matches(&Version::new(0, 9, 0)));
+
+        let r = req(">1.2, <=1.4");
+        assert!(!r.matches(&Version::new(1, 2, 9)));
+        assert!(r.matches(&Version::new(1, 3, 0)));
+        assert!(r.matches(&Version::new(1, 4, 7)));
+        assert!(!r.matches(&Version::new(1, 5, 0)));
+    }
+
+    #[test]
+    fn version_req_exact() {
+        let r = req("1.2.3");
+        assert!(r.matches(&Version::new(1, 2, 3)));
+        assert!(!r.matches(&Version::new(1, 2, 4)));
+        assert_eq!(r, VersionReq::exact(&Version::new(1, 2, 3)));
+
+        let r = req("=1.2");
+        assert!(r.matches(&Version::new(1, 2, 7)));
+        assert!(!r.matches(&Version::new(1, 3, 0)));
+
+        assert!(req("*").matches(&Version::new(7, 0, 0)));
+    }
+
+    #[test]
+    fn version_req_parse() {
+        assert_eq!(req(" ^1.2 ,<1.5.0").to_string(), "^1.2, <1.5.0");
+        assert_eq!("1.2.3.4".parse::<VersionReq>(), Err(Error::BadVersionReq(
+                "1.2.3.4".to_string(), "too many version numbers".to_string())));
+        assert!("".parse::<VersionReq>().is_err());
+        assert!(">=1.x".parse::<VersionReq>().is_err());
+        assert!("^1.2,".parse::<VersionReq>().is_err());
+        assert!("^+1".parse::<VersionReq>().is_err());
+    }
+}
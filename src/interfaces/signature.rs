@@ -0,0 +1,389 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Error;
+
+/// Type of the function parameter or the return value.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Type {
+
+    /// No value, written as `()`.
+    Unit,
+
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+
+    /// String, written as `str`.
+    Str,
+
+    /// Array of bytes, written as `bytes`.
+    Bytes,
+
+    /// List of values of the same type, written as `list<u32>`.
+    List(Box<Type>),
+
+    /// Structure with ordered named fields, written as
+    /// `struct(x: u32, y: u32)`.
+    Struct(Vec<(String, Type)>),
+
+    /// Handle of the channel, written as `chan`.
+    Channel,
+}
+
+/// Signature of the function: ordered named parameters and the type of
+/// the return value. Written as `(path: str, flags: u32) -> chan`. Return
+/// type may be omitted if it is `()`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Signature {
+    params: Vec<(String, Type)>,
+    ret: Type,
+}
+
+/// Greatest nesting of lists and structures that is parsed. Deeper types
+/// are refused so that parsing does not overflow the stack.
+const MAX_TYPE_DEPTH: usize = 32;
+
+/// Word or punctuation of the signature.
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Punct(&'static str),
+}
+
+/// Parser of the signature or the type.
+struct Parser<'a> {
+    text: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+
+    /// Number of lists and structures the parser is in.
+    depth: usize,
+}
+
+impl Signature {
+
+    /// Create new signature.
+    pub fn new(params: Vec<(String, Type)>, ret: Type) -> Self {
+        Signature {
+            params,
+            ret,
+        }
+    }
+
+    /// Parameters of the function with their names.
+    pub fn params(&self) -> &[(String, Type)] {
+        &self.params
+    }
+
+    /// Type of the return value.
+    pub fn ret(&self) -> &Type {
+        &self.ret
+    }
+}
+
+impl Default for Signature {
+
+    /// Signature without parameters and return value.
+    fn default() -> Self {
+        Signature::new(Vec::new(), Type::Unit)
+    }
+}
+
+impl<'a> Parser<'a> {
+
+    fn new(text: &'a str) -> Result<Self, Error> {
+        let mut tokens = Vec::new();
+        let mut chars = text.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            let punct = match c {
+                '(' => "(",
+                ')' => ")",
+                '<' => "<",
+                '>' => ">",
+                ',' => ",",
+                ':' => ":",
+                '-' if chars.peek() == Some(&'>') => {
+                    chars.next();
+                    "->"
+                },
+                c if c.is_whitespace() => continue,
+                c if c.is_alphanumeric() || c == '_' => {
+                    let mut word = c.to_string();
+                    while let Some(&c) = chars.peek() {
+                        if !c.is_alphanumeric() && c != '_' {
+                            break;
+                        }
+                        word.push(c);
+                        chars.next();
+                    }
+                    tokens.push(Token::Word(word));
+                    continue;
+                },
+                c => return Err(Error::BadSignature(text.to_string(),
+                        format!("unexpected character '{}'", c))),
+            };
+            tokens.push(Token::Punct(punct));
+        }
+
+        Ok(Parser {
+            text,
+            tokens,
+            pos: 0,
+            depth: 0,
+        })
+    }
+
+    fn error<S: Into<String>>(&self, reason: S) -> Error {
+        Error::BadSignature(self.text.to_string(), reason.into())
+    }
+
+    /// Take the punctuation if it is next.
+    fn take(&mut self, punct: &str) -> bool {
+        match self.tokens.get(self.pos) {
+            Some(Token::Punct(p)) if *p == punct => {
+                self.pos += 1;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), Error> {
+        if self.take(punct) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", punct)))
+        }
+    }
+
+    fn word(&mut self, what: &str) -> Result<String, Error> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Word(word)) => {
+                self.pos += 1;
+                Ok(word.clone())
+            },
+            _ => Err(self.error(format!("expected {}", what))),
+        }
+    }
+
+    /// Check that all text was read.
+    fn end(&self) -> Result<(), Error> {
+        if self.pos < self.tokens.len() {
+            Err(self.error("unexpected text at the end"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Read named fields in parentheses.
+    fn fields(&mut self) -> Result<Vec<(String, Type)>, Error> {
+        self.expect("(")?;
+        let mut fields = Vec::new();
+        if self.take(")") {
+            return Ok(fields);
+        }
+
+        loop {
+            let name = self.word("name")?;
+            if fields.iter().any(|(n, _)| *n == name) {
+                return Err(self.error(format!("duplicate name '{}'", name)));
+            }
+            self.expect(":")?;
+            fields.push((name, self.ty()?));
+
+            if self.take(")") {
+                return Ok(fields);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn ty(&mut self) -> Result<Type, Error> {
+        if self.take("(") {
+            self.expect(")")?;
+            return Ok(Type::Unit);
+        }
+
+        let ty = match self.word("type")?.as_str() {
+            "bool" => Type::Bool,
+            "u8" => Type::U8,
+            "u16" => Type::U16,
+            "u32" => Type::U32,
+            "u64" => Type::U64,
+            "i8" => Type::I8,
+            "i16" => Type::I16,
+            "i32" => Type::I32,
+            "i64" => Type::I64,
+            "f32" => Type::F32,
+            "f64" => Type::F64,
+            "str" => Type::Str,
+            "bytes" => Type::Bytes,
+            "chan" => Type::Channel,
+            "list" => {
+                self.enter()?;
+                self.expect("<")?;
+                let item = self.ty()?;
+                self.expect(">")?;
+                self.depth -= 1;
+                Type::List(Box::new(item))
+            },
+            "struct" => {
+                self.enter()?;
+                let fields = self.fields()?;
+                self.depth -= 1;
+                Type::Struct(fields)
+            },
+            other => return Err(self.error(format!(
+                    "unknown type '{}'", other))),
+        };
+        Ok(ty)
+    }
+
+    /// Go one level deeper into the nested type.
+    fn enter(&mut self) -> Result<(), Error> {
+        if self.depth == MAX_TYPE_DEPTH {
+            return Err(self.error("types are nested too deep"));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn signature(&mut self) -> Result<Signature, Error> {
+        let params = self.fields()?;
+        let ret = if self.take("->") {
+            self.ty()?
+        } else {
+            Type::Unit
+        };
+        Ok(Signature::new(params, ret))
+    }
+}
+
+impl FromStr for Type {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut parser = Parser::new(s)?;
+        let ty = parser.ty()?;
+        parser.end()?;
+        Ok(ty)
+    }
+}
+
+impl FromStr for Signature {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut parser = Parser::new(s)?;
+        let signature = parser.signature()?;
+        parser.end()?;
+        Ok(signature)
+    }
+}
+
+/// Write named fields in parentheses.
+fn write_fields(f: &mut fmt::Formatter, fields: &[(String, Type)])
+        -> fmt::Result {
+    write!(f, "(")?;
+    for (i, (name, ty)) in fields.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}: {}", name, ty)?;
+    }
+    write!(f, ")")
+}
+
+impl fmt::Display for Type {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Type::*;
+
+        let name = match self {
+            Unit => "()",
+            Bool => "bool",
+            U8 => "u8",
+            U16 => "u16",
+            U32 => "u32",
+            U64 => "u64",
+            I8 => "i8",
+            I16 => "i16",
+            I32 => "i32",
+            I64 => "i64",
+            F32 => "f32",
+            F64 => "f64",
+            Str => "str",
+            Bytes => "bytes",
+            Channel => "chan",
+            List(item) => return write!(f, "list<{}>", item),
+            Struct(fields) => {
+                write!(f, "struct")?;
+                return write_fields(f, fields);
+            },
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Signature {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_fields(f, &self.params)?;
+        write!(f, " -> {}", self.ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_parse() {
+        let s: Signature = "(path: str, mode: list<struct(a: u8, b: chan)>) \
+                -> bytes".parse().unwrap();
+        assert_eq!(s.params().len(), 2);
+        assert_eq!(s.params()[1].1, Type::List(Box::new(Type::Struct(vec![
+                ("a".to_string(), Type::U8),
+                ("b".to_string(), Type::Channel),
+        ]))));
+        assert_eq!(*s.ret(), Type::Bytes);
+        assert_eq!(s.to_string(),
+                "(path: str, mode: list<struct(a: u8, b: chan)>) -> bytes");
+        assert_eq!(s.to_string().parse(), Ok(s));
+
+        let s: Signature = "()".parse().unwrap();
+        assert_eq!(s, Signature::default());
+        assert_eq!(s.to_string(), "() -> ()");
+    }
+
+    #[test]
+    fn signature_errors() {
+        assert_eq!("(a: u128)".parse::<Signature>(), Err(Error::BadSignature(
+                "(a: u128)".to_string(), "unknown type 'u128'".to_string())));
+        assert!("(a: u8, a: u8)".parse::<Signature>().is_err());
+        assert!("(a u8)".parse::<Signature>().is_err());
+        assert!("(a: list<u8)".parse::<Signature>().is_err());
+        assert!("() -> u8 u8".parse::<Signature>().is_err());
+        assert!("(a: u8) => u8".parse::<Signature>().is_err());
+        assert!("".parse::<Type>().is_err());
+
+        let nested = |depth| {
+            "list<".repeat(depth) + "u8" + &">".repeat(depth)
+        };
+        assert!(nested(MAX_TYPE_DEPTH).parse::<Type>().is_ok());
+        let text = nested(MAX_TYPE_DEPTH + 1);
+        assert_eq!(text.parse::<Type>(), Err(Error::BadSignature(
+                text.clone(), "types are nested too deep".to_string())));
+        assert!("struct(a: ".repeat(100000).parse::<Type>().is_err());
+    }
+}
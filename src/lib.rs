@@ -1,4 +1,4 @@
-use std::collections::LinkedList;
+use std::collections::{BTreeMap, BTreeSet, LinkedList};
 
 /// Application thread list operations.
 pub mod threads;
@@ -7,6 +7,7 @@ pub use crate::threads::{
     Thread,
     Key as ThreadKey,
     State as ThreadState,
+    WakeReason,
 };
 
 /// Interface set and operations.
@@ -17,6 +18,11 @@ pub use crate::interfaces:: {
     Interface,
     Func as InterfaceFunc,
     InterfaceSet,
+    VersionReq,
+    Change as InterfaceChange,
+    Compatibility,
+    Signature,
+    Type,
 };
 
 /// Paths to packages which contains interfaces and processes.
@@ -24,8 +30,11 @@ pub mod path;
 pub use crate::path:: {
     Path,
     RcPath,
+    RelPath,
     PathIter,
     PackageTree,
+    Walk as PackageWalk,
+    Entries as PackageEntries,
 };
 
 /// Operations on channels between threads.
@@ -34,6 +43,8 @@ pub use crate::channels::{
     Channel,
     Key as ChannelKey,
     ChannelSet,
+    Sent,
+    Received,
 };
 
 /// Process data and operations on processes.
@@ -42,37 +53,119 @@ pub use crate::process::{
     Key as ProcessKey,
     Process,
     Set as ProcessSet,
+    Removal as ProcessRemoval,
     ImplementationConflicts,
+    FuncConflicts,
 };
 
 /// Operations related to waiting threads and channel lock relations.
 pub mod wait;
 pub use crate::wait::{
+    Cycle,
+    Analysis as WaitAnalysis,
+    DeadlockSet,
     WaitDependency,
     WaitMap,
     Graph,
     GraphNode,
 };
 
+/// Errors of operations on the network.
+pub mod error;
+pub use crate::error::Error;
+
+/// Virtual time of the network.
+pub mod clock;
+pub use crate::clock::{
+    Clock,
+    Time,
+};
+
+/// Scheduling of threads that wait for processor time.
+pub mod scheduler;
+pub use crate::scheduler::{
+    Scheduler,
+    RoundRobin,
+    PriorityScheduler,
+    Priority,
+};
+
+/// Access control policies of the packages.
+pub mod policy;
+pub use crate::policy::{
+    Access,
+    Policy,
+};
+
+/// Language of interface definitions.
+pub mod idl;
+
+/// Saving the network to the text snapshot and loading it back.
+pub mod snapshot;
+pub use crate::snapshot::Payload;
+
 /// Network that contains all threads, channels, packages and interfaces.
-#[derive(Default)]
-pub struct Network {
+///
+/// Messages sent through the channels of the network carry payload
+/// of type `M`.
+pub struct Network<M = ()> {
     threads: ThreadSet,
     processes: ProcessSet,
     interfaces: InterfaceSet,
-    channels: ChannelSet,
+    channels: ChannelSet<M>,
     packages: PackageTree,
     wait_deps: WaitMap,
+    clock: Clock,
+
+    /// Run queue of the threads that wait for processor time.
+    scheduler: Box<dyn Scheduler>,
+
+    /// Threads that are running on the virtual processors.
+    running: BTreeSet<ThreadKey>,
+
+    /// Number of virtual processors.
+    cpus: usize,
 
     next_process_key: ProcessKey,
     next_channel_key: ChannelKey,
 }
 
+/// Summary of the interface removal from the network.
+pub struct InterfaceRemoval {
+    interfaces: BTreeMap<InterfaceKey, Interface>,
+    processes: BTreeSet<ProcessKey>,
+}
+
 impl Network {
 
+    /// Create new network with channels that carry no payload. Use
+    /// `Network::default` to create network with other payload type.
     pub fn new() -> Self {
         Default::default()
     }
+}
+
+impl<M> Default for Network<M> {
+
+    fn default() -> Self {
+        Network {
+            threads: Default::default(),
+            processes: Default::default(),
+            interfaces: Default::default(),
+            channels: Default::default(),
+            packages: Default::default(),
+            wait_deps: Default::default(),
+            clock: Default::default(),
+            scheduler: Box::new(RoundRobin::new()),
+            running: Default::default(),
+            cpus: 1,
+            next_process_key: Default::default(),
+            next_channel_key: Default::default(),
+        }
+    }
+}
+
+impl<M> Network<M> {
 
     /// Threads registered in the network.
     pub fn threads(&self) -> &ThreadSet {
@@ -90,7 +183,7 @@ impl Network {
     }
 
     /// Channels created in the network.
-    pub fn channels(&self) -> &ChannelSet {
+    pub fn channels(&self) -> &ChannelSet<M> {
         &self.channels
     }
 
@@ -104,164 +197,750 @@ impl Network {
         &self.wait_deps
     }
 
-    /// Register new thread in given process.
+    /// Find all deadlocks of the network. Waiting never forms a deadlock
+    /// but it may be present in the network loaded from the snapshot.
+    pub fn analyze(&self) -> WaitAnalysis {
+        self.wait_deps.analyze()
+    }
+
+    /// Virtual clock of the network.
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    /// Scheduler that holds the run queue of the network.
+    pub fn scheduler(&self) -> &dyn Scheduler {
+        self.scheduler.as_ref()
+    }
+
+    /// Replace the scheduler of the network. All sleeping threads are
+    /// moved to the run queue of the new scheduler.
+    pub fn set_scheduler(&mut self, scheduler: Box<dyn Scheduler>) {
+        self.scheduler = scheduler;
+        for (key, thread) in self.threads.iter() {
+            self.scheduler.set_priority(key, thread.effective_priority());
+            if *thread.state() == ThreadState::Sleep {
+                self.scheduler.push(*key);
+            }
+        }
+    }
+
+    /// Number of virtual processors. It is the maximal number of threads
+    /// that can be active at once.
+    pub fn cpu_count(&self) -> usize {
+        self.cpus
+    }
+
+    /// Change the number of virtual processors. If there are more active
+    /// threads than processors, no thread gets scheduled until enough of
+    /// them stop.
     ///
-    /// # Returns
-    /// None is returned if no such process was found. Value is
-    /// returned when thread was registered successfully.
-    pub fn new_thread(&mut self, thread: Thread, process: &ProcessKey)
-            -> Option<ThreadKey> {
-        let process = self.processes.get_mut(process);
-        if process.is_none() {
+    /// # Panics
+    /// Panic occurs when count is zero.
+    pub fn set_cpu_count(&mut self, count: usize) {
+        assert!(count > 0, "network needs at least one processor");
+        self.cpus = count;
+    }
+
+    /// Threads that are currently active.
+    pub fn running_threads(&self) -> &BTreeSet<ThreadKey> {
+        &self.running
+    }
+
+    /// Change own priority of the thread. Priority is passed to the
+    /// threads that this thread waits for.
+    ///
+    /// # Errors
+    /// `UnknownThread` is returned if thread was not found.
+    pub fn set_priority(&mut self, thread: &ThreadKey, priority: Priority)
+            -> Result<(), Error> {
+        self.existing_thread(thread)?;
+        self.threads.get_mut(thread).unwrap().set_priority(priority);
+        let affected = self.lent_to(thread);
+        self.inherit_priorities(&affected);
+
+        let priority = self.threads.get(thread).unwrap().effective_priority();
+        self.scheduler.set_priority(thread, priority);
+        Ok(())
+    }
+
+    /// Take the next thread from the run queue and make it active.
+    ///
+    /// Returns `None` if all processors are busy or no thread waits for
+    /// processor time.
+    pub fn schedule(&mut self) -> Option<ThreadKey> {
+        if self.running.len() >= self.cpus {
             return None;
         }
-        let process = process.unwrap();
 
+        let thread = self.scheduler.pop()?;
+        self.change_thread_state_remove_deps(&thread, ThreadState::Active)
+                .unwrap();
+        Some(thread)
+    }
+
+    /// Move the clock forward by given duration. All threads which wait
+    /// timeout expires become sleeping and waiting for processor time.
+    /// Their wake reason is set to `WakeReason::TimedOut`.
+    ///
+    /// Returns list of threads that woke up.
+    pub fn advance_time(&mut self, dt: Time) -> LinkedList<ThreadKey> {
+        let now = self.clock.advance(dt);
+
+        let mut list = LinkedList::new();
+        for (key, thread) in self.threads.iter() {
+            if let ThreadState::WaitWithTimeout(chan, deadline) = thread.state() {
+                if *deadline <= now {
+                    list.push_back((*key, *chan));
+                }
+            }
+        }
+
+        list.into_iter().map(|(key, chan)| {
+            self.wake_thread(&key, WakeReason::TimedOut(chan)).unwrap();
+            key
+        }).collect()
+    }
+
+    /// Register new thread in given process.
+    ///
+    /// # Errors
+    /// `UnknownProcess` is returned if no such process was found.
+    pub fn new_thread(&mut self, thread: Thread, process: &ProcessKey)
+            -> Result<ThreadKey, Error> {
+        let process = match self.processes.get_mut(process) {
+            Some(p) => p,
+            None => return Err(Error::UnknownProcess(*process)),
+        };
+
+        let priority = thread.effective_priority();
         let thread_key = self.threads.add(thread);
-        process.attach_thread(thread_key.clone());
-        Some(thread_key)
+        process.attach_thread(thread_key);
+        self.scheduler.set_priority(&thread_key, priority);
+        self.update_run_queue(&thread_key);
+        Ok(thread_key)
+    }
+
+    /// Remove thread from the network. Thread is detached from it's process,
+    /// from all channels where it participates and from the wait map.
+    /// Channels that are left without participants are removed too.
+    ///
+    /// Remaining participants of the channels may all be locked now, so
+    /// relations of these channels are registered again. Such relations
+    /// may form a deadlock which can be found with `analyze`.
+    ///
+    /// # Returns
+    /// The set of peers that were waiting for a signal on any channel
+    /// shared with the removed thread. These peers may never receive
+    /// a signal from the removed thread now.
+    ///
+    /// # Errors
+    /// `UnknownThread` is returned if no such thread was found or if any
+    /// of the participants of its channels is not registered.
+    /// `UnknownChannel` is returned if any of its channels is not
+    /// registered. Nothing is changed in these cases.
+    pub fn remove_thread(&mut self, thread_key: &ThreadKey)
+            -> Result<BTreeSet<ThreadKey>, Error> {
+        let channels = self.existing_thread(thread_key)?.channels().clone();
+
+        // Collect peers that were blocked on the channels of this thread.
+        let mut blocked = BTreeSet::new();
+        for ch in channels.iter() {
+            for participant in self.existing_channel(ch)?.participants() {
+                if participant == thread_key {
+                    continue;
+                }
+                if self.existing_thread(participant)?.is_waiting_channel(ch) {
+                    blocked.insert(*participant);
+                }
+            }
+        }
+
+        // Lookups that may fail are all done above, so the thread is never
+        // left half removed.
+        let affected = self.lent_to(thread_key);
+        self.remove_from_wait_dep(thread_key);
+
+        for ch in channels.iter() {
+            let channel = self.channels.get_mut(ch).unwrap();
+            channel.remove_participant(*thread_key);
+            if channel.participants().is_empty() {
+                self.drop_channel(ch);
+            }
+        }
+
+        for (_, process) in self.processes.iter_mut() {
+            if process.detach_thread(thread_key) {
+                break;
+            }
+        }
+
+        self.threads.remove(thread_key);
+        self.scheduler.forget(thread_key);
+        self.running.remove(thread_key);
+
+        for ch in channels.iter() {
+            if self.channels.get(ch).is_some() {
+                self.restore_channel_relations(ch);
+            }
+        }
+        self.inherit_priorities(&affected);
+        Ok(blocked)
     }
 
-    /// Register new process in the network.
-    pub fn new_process(&mut self, process: Process) -> ProcessKey {
+    /// Register new process in the network. Process must have no threads,
+    /// they are added with `new_thread`.
+    ///
+    /// # Errors
+    /// `ProcessHasThreads` is returned if any thread is attached to the
+    /// process.
+    /// `PermissionDenied` is returned if policies do not allow the process
+    /// to implement any of it's interfaces.
+    pub fn new_process(&mut self, process: Process)
+            -> Result<ProcessKey, Error> {
+        if !process.threads().is_empty() {
+            return Err(Error::ProcessHasThreads);
+        }
+        for key in process.implementations() {
+            self.check_access(key.path(), Access::Implement, process.path())?;
+        }
+
         let new_key = self.next_process_key;
         self.next_process_key += 1;
-        self.processes.insert(new_key.clone(), process);
-        new_key
+        self.packages.add_process(process.path(), new_key);
+        self.processes.insert(new_key, process).unwrap();
+        Ok(new_key)
     }
 
-    /// Register new channel in the network.
+    /// Add the interface to the implementations of registered process.
+    /// Return true if it was not implemented before and false otherwise.
+    ///
+    /// # Errors
+    /// `UnknownProcess` is returned if no such process was found.
+    /// `PermissionDenied` is returned if policies do not allow the process
+    /// to implement the interface.
+    pub fn add_implementation(&mut self, process: &ProcessKey,
+            key: InterfaceKey) -> Result<bool, Error> {
+        let path = match self.processes.get(process) {
+            Some(p) => p.path().clone(),
+            None => return Err(Error::UnknownProcess(*process)),
+        };
+        self.check_access(key.path(), Access::Implement, &path)?;
+        Ok(self.processes.get_mut(process).unwrap().add_implementation(key))
+    }
+
+    /// Add the function that is provided by registered process for given
+    /// interface. Interface is added to the implementations of the process
+    /// if it is not there yet. Return true if function was not provided
+    /// before and false otherwise.
+    ///
+    /// # Errors
+    /// `UnknownProcess` is returned if no such process was found.
+    /// `PermissionDenied` is returned if policies do not allow the process
+    /// to implement the interface.
+    pub fn add_fn_implementation(&mut self, process: &ProcessKey,
+            interface: InterfaceKey, func: InterfaceFunc)
+            -> Result<bool, Error> {
+        let path = match self.processes.get(process) {
+            Some(p) => p.path().clone(),
+            None => return Err(Error::UnknownProcess(*process)),
+        };
+        self.check_access(interface.path(), Access::Implement, &path)?;
+        Ok(self.processes.get_mut(process).unwrap()
+                .add_fn_implementation(interface, func))
+    }
+
+    /// Attach access control policy to the package. Policy applies to
+    /// the package and it's sub-packages that have no own policy. It is
+    /// checked on later operations only, existing implementations and
+    /// channels are kept.
+    pub fn set_policy(&mut self, path: &RcPath, policy: Policy) {
+        self.packages.set_policy(path, policy);
+    }
+
+    /// Detach access control policy from the package and return it.
+    pub fn remove_policy(&mut self, path: &RcPath) -> Option<Policy> {
+        self.packages.remove_policy(path)
+    }
+
+    /// Remove process with all it's threads from the network. Package of
+    /// the process is removed from the package tree if no other process
+    /// or interface is located in it.
     ///
     /// # Returns
-    /// None is returned if any of partcipant threads were not found.
-    /// Some is returned if channel was successfully registered.
-    pub fn new_channel(&mut self, channel: Channel) -> Option<ChannelKey> {
+    /// Summary of what was removed.
+    ///
+    /// # Errors
+    /// `UnknownProcess` is returned if no such process was found.
+    pub fn remove_process(&mut self, process: &ProcessKey)
+            -> Result<ProcessRemoval, Error> {
+        // Keys that are not registered in the network are skipped, so
+        // the teardown does not stop half done on them.
+        let threads: BTreeSet<ThreadKey> = match self.processes.get(process) {
+            Some(process) => process.threads().iter()
+                    .filter(|thread| self.threads.get(thread).is_some())
+                    .cloned()
+                    .collect(),
+            None => return Err(Error::UnknownProcess(*process)),
+        };
+
+        let mut blocked = BTreeSet::new();
+        for thread in threads.iter() {
+            let mut peers = self.remove_thread(thread)?;
+            blocked.append(&mut peers);
+        }
+        let blocked = blocked.difference(&threads).cloned().collect();
+
+        let key = *process;
+        let process = self.processes.remove(process)?;
+        let package_removed = self.packages.remove_process(process.path(),
+                &key);
+
+        Ok(ProcessRemoval::new(process, threads, blocked, package_removed))
+    }
+
+    /// Register new interface in the network. Interface is recorded in the
+    /// package tree.
+    ///
+    /// # Errors
+    /// Errors of `InterfaceSet::add_interface` are returned.
+    pub fn add_interface(&mut self, key: InterfaceKey, interface: Interface)
+            -> Result<(), Error> {
+        self.interfaces.add_interface(key.clone(), interface)?;
+        self.packages.add_interface(&key);
+        Ok(())
+    }
+
+    /// Remove interface from the network. Interface is not removed while
+    /// other interfaces require it or processes implement it. Package of
+    /// the interface is removed if nothing else is located in it.
+    ///
+    /// # Errors
+    /// `UnknownInterface` is returned if no such interface was found.
+    /// `InterfaceInUse` is returned with all dependents of the interface.
+    pub fn remove_interface(&mut self, key: &InterfaceKey)
+            -> Result<InterfaceRemoval, Error> {
+        if self.interfaces.interface(key).is_none() {
+            return Err(Error::UnknownInterface(key.clone()));
+        }
+
+        let interfaces = self.interfaces.dependents(key);
+        let processes: BTreeSet<ProcessKey> = self.processes.processes().iter()
+                .filter(|(_, p)| p.implementations().contains(key))
+                .map(|(k, _)| *k)
+                .collect();
+        if !interfaces.is_empty() || !processes.is_empty() {
+            return Err(Error::InterfaceInUse(key.clone(), interfaces,
+                    processes));
+        }
+
+        let interface = self.interfaces.remove_interface(key)?;
+        self.packages.remove_interface(key);
+
+        let mut interfaces = BTreeMap::new();
+        interfaces.insert(key.clone(), interface);
+        Ok(InterfaceRemoval { interfaces, processes })
+    }
+
+    /// Remove interface together with all interfaces that require it
+    /// directly or through other prerequisites. Removed interfaces are
+    /// dropped from the implementations of all processes. Packages that
+    /// become unused are removed.
+    ///
+    /// # Errors
+    /// `UnknownInterface` is returned if no such interface was found.
+    pub fn remove_interface_cascade(&mut self, key: &InterfaceKey)
+            -> Result<InterfaceRemoval, Error> {
+        let interfaces = self.interfaces.remove_cascade(key)?;
+
+        let mut processes = BTreeSet::new();
+        for (process_key, process) in self.processes.iter_mut() {
+            for key in interfaces.keys() {
+                if process.remove_implementation(key) {
+                    processes.insert(*process_key);
+                }
+            }
+        }
+
+        for key in interfaces.keys() {
+            self.packages.remove_interface(key);
+        }
+        Ok(InterfaceRemoval { interfaces, processes })
+    }
+
+    /// Register new channel in the network.
+    ///
+    /// Creator of the channel opens it to the other participants. It must
+    /// be allowed to connect to at least one interface implemented by the
+    /// process of each other participant that implements any. Use
+    /// `connect` to check the access to the particular interface.
+    ///
+    /// # Errors
+    /// `UnknownThread` is returned if any of participant threads were not
+    /// found.
+    /// `ThreadHasNoProcess` is returned if the creator belongs to no
+    /// process.
+    /// `PermissionDenied` is returned if policies do not allow the process
+    /// of the creator to connect to any interface of the process of
+    /// another participant.
+    pub fn new_channel(&mut self, channel: Channel<M>)
+            -> Result<ChannelKey, Error> {
         let participants = channel.participants();
 
         // Check if all participants are really registered in this network.
         for participant in participants {
-            if self.threads.get(participant).is_none() {
-                return None;
+            self.existing_thread(participant)?;
+        }
+
+        // Creator connects to the implementers among the others.
+        let (client_key, client) = self.process_of(&channel.creator())?;
+        let servers = self.processes.processes().iter()
+                .filter(|(key, p)| **key != client_key
+                        && !p.threads().is_disjoint(participants));
+        for (_, server) in servers {
+            let mut keys = server.implementations().iter();
+            let permitted = keys.clone().any(|key| self.packages.permits(
+                    key.path(), Access::Connect, client.path()));
+            if let (false, Some(key)) = (permitted, keys.next()) {
+                return Err(Error::PermissionDenied(client.path().clone(),
+                        Access::Connect, key.path().clone()));
             }
         }
 
-        let participants = participants.clone();
+        Ok(self.add_channel(channel))
+    }
+
+    /// Register new channel without checking the policies. All participant
+    /// threads must exist.
+    fn add_channel(&mut self, channel: Channel<M>) -> ChannelKey {
+        let participants = channel.participants().clone();
 
         let next_channel_key = &mut self.next_channel_key;
-        let channel_key = next_channel_key.clone();
-        self.channels.insert(channel_key.clone(), channel);
-        self.wait_deps.add_channel(channel_key.clone(), Default::default());
+        let channel_key = *next_channel_key;
+        self.channels.insert(channel_key, channel);
+        self.wait_deps.add_channel(channel_key, Default::default());
         *next_channel_key += 1;
 
         // Register channel to all threads.
         for participant in participants {
             let thread = self.threads.get_mut(&participant).unwrap();
-            thread.channels_mut().insert(channel_key.clone());
+            thread.channels_mut().insert(channel_key);
         }
 
-        Some(channel_key)
+        channel_key
     }
 
-    /// Try put thread asleep.
+    /// Connect the client thread to the implementer of the interface with
+    /// given path and the version that satisfies the requirement. New
+    /// channel between the client and a thread of implementing process is
+    /// created.
+    ///
+    /// Only interfaces registered in the network are considered. Highest
+    /// matching version is preferred. Among the threads that implement it
+    /// the one with the least number of channels is chosen. Other threads
+    /// of the process of the client are chosen as well, only the client
+    /// itself is never connected to.
     ///
     /// # Returns
-    /// Some if thread was found and successfully put asleep.
-    /// None if thread was not found.
-    pub fn sleep_thread(&mut self, thread: &ThreadKey) -> Option<()> {
+    /// Key of the created channel.
+    ///
+    /// # Errors
+    /// `UnknownThread` is returned if client thread was not found.
+    /// `ThreadHasNoProcess` is returned if client belongs to no process.
+    /// `PermissionDenied` is returned if policies do not allow the process
+    /// of the client to connect to implementers of the interface.
+    /// `NoImplementer` is returned if no thread other than the client
+    /// implements the registered interface.
+    pub fn connect(&mut self, client: &ThreadKey, path: &RcPath,
+            req: &VersionReq) -> Result<ChannelKey, Error> {
+        let (_, client_process) = self.process_of(client)?;
+        self.check_access(path, Access::Connect, client_process.path())?;
+
+        // Best server: highest version, then least channels, then lowest key.
+        let mut best: Option<(&Version, usize, ThreadKey)> = None;
+        for process in self.processes.processes().values() {
+            let version = process.implementations().iter()
+                    .filter(|key| key.path() == path
+                            && req.matches(key.version())
+                            && self.interfaces.interfaces().contains_key(key))
+                    .map(|key| key.version())
+                    .max();
+            let version = match version {
+                Some(version) => version,
+                None => continue,
+            };
+
+            for thread in process.threads() {
+                if thread == client {
+                    continue;
+                }
+                let load = match self.threads.get(thread) {
+                    Some(thread) => thread.channels().len(),
+                    None => continue,
+                };
+                let better = match best {
+                    Some((best_version, best_load, best_thread)) =>
+                        version > best_version
+                        || (version == best_version
+                            && (load, *thread)
+                                < (best_load, best_thread)),
+                    None => true,
+                };
+                if better {
+                    best = Some((version, load, *thread));
+                }
+            }
+        }
+
+        let server = match best {
+            Some((_, _, server)) => server,
+            None => return Err(Error::NoImplementer(path.clone(), req.clone())),
+        };
+        let mut channel = Channel::new(*client);
+        channel.add_participant(server);
+        Ok(self.add_channel(channel))
+    }
+
+    /// Connect the client thread to the implementer of the interface with
+    /// exactly given key. See `connect`.
+    pub fn connect_key(&mut self, client: &ThreadKey, key: &InterfaceKey)
+            -> Result<ChannelKey, Error> {
+        self.connect(client, key.path(), &VersionReq::exact(key.version()))
+    }
+
+    /// Put thread asleep. Thread is added to the run queue and waits for
+    /// processor time.
+    ///
+    /// # Errors
+    /// `UnknownThread` is returned if thread was not found.
+    pub fn sleep_thread(&mut self, thread: &ThreadKey) -> Result<(), Error> {
         self.change_thread_state_remove_deps(thread, ThreadState::Sleep)
     }
 
-    pub fn active_thread(&mut self, thread: &ThreadKey) -> Option<()> {
+    /// Make thread active. Thread is removed from the run queue and takes
+    /// one of the processors.
+    ///
+    /// # Errors
+    /// `UnknownThread` is returned if thread was not found.
+    /// `NoFreeProcessor` is returned if all processors are busy.
+    pub fn active_thread(&mut self, thread: &ThreadKey) -> Result<(), Error> {
+        if *self.existing_thread(thread)?.state() != ThreadState::Active
+                && self.running.len() >= self.cpus {
+            return Err(Error::NoFreeProcessor(*thread));
+        }
         self.change_thread_state_remove_deps(thread, ThreadState::Active)
     }
 
+    /// Put thread in the waiting state until the signal from given channel
+    /// is received. If timeout is given, thread wakes up when it expires
+    /// on `advance_time`. Threads that wait with timeout are not considered
+    /// locked when looking for deadlocks.
+    ///
+    /// # Errors
+    /// `UnknownThread` or `UnknownChannel` is returned if thread or channel
+    /// was not found. `NotParticipant` is returned if thread does not
+    /// participate in the channel. `Deadlock` is returned if waiting would
+    /// cause a deadlock. In this case all changes are reverted.
     pub fn wait_thread(&mut self, thread_key: &ThreadKey,
-        signal_source: &ChannelKey, timer: bool
-    ) -> Result<Option<()>, ()> {
-        if timer == true {
-            return Ok(self.change_thread_state_remove_deps(thread_key,
-                    ThreadState::WaitWithTimeout(signal_source.clone())));
-        }
-
-        if self.channels.get(signal_source).is_none() {
-            return Ok(None);
-        }
-
-        let thread = self.thread(thread_key);
-        if thread.is_none() {
-            return Ok(None);
-        }
-        let thread = thread.unwrap();
-
-        // Register channel relations if all threads in channel are locked.
-        let wd = unsafe { &mut *(&self.wait_deps as *const _ as *mut WaitMap) };
-        wd.add_waiter(signal_source.clone(), thread_key.clone());
-        let mut err = false;
-        for ch in thread.channels().iter() {
-            let participant_count =
-                    self.channels.get(ch).unwrap().participants().len();
-            if participant_count == wd.channel_wait_map().len() {
-                let result = wd.add_channel_relation(signal_source, ch);
-                if result.is_err() {
-                    err = true;
-                    break;
-                } else if result.unwrap() == false {
-                    panic!("Couldn't find destination channel which is known
-                    to be registered. Channel number: {}", ch);
-                }
+        signal_source: &ChannelKey, timeout: Option<Time>
+    ) -> Result<(), Error> {
+        let thread = self.existing_thread(thread_key)?;
+        let chan = self.existing_channel(signal_source)?;
+        if !chan.participants().contains(thread_key) {
+            return Err(Error::NotParticipant(
+                    *thread_key, *signal_source));
+        }
+
+        if let Some(timeout) = timeout {
+            let deadline = self.clock.deadline(timeout);
+            return self.change_thread_state_remove_deps(thread_key,
+                    ThreadState::WaitWithTimeout(*signal_source,
+                            deadline));
+        }
+
+        let old_state = *thread.state();
+        let channels = thread.channels().clone();
+
+        self.change_thread_state_remove_deps(thread_key,
+                ThreadState::WaitWithoutTimeout(*signal_source))?;
+        self.wait_deps.add_waiter(*signal_source, *thread_key)?;
+
+        // Channels of this thread may become locked now.
+        let mut result = Ok(());
+        for ch in channels.iter() {
+            result = self.add_channel_relations(ch);
+            if result.is_err() {
+                break;
             }
         }
 
         // Revert changes if loop occured.
-        if err {
-            for ch in thread.channels().iter() {
-                wd.remove_channel_relation(signal_source, ch);
+        if result.is_err() {
+            self.change_thread_state_remove_deps(thread_key, old_state)?;
+
+            // Previous wait was allowed, so it is registered again as it was.
+            if let ThreadState::WaitWithoutTimeout(source) = old_state {
+                self.wait_deps.add_waiter(source, *thread_key)?;
+                for ch in channels.iter() {
+                    self.restore_channel_relations(ch);
+                }
             }
-            Err(())
-        } else {
-            Ok(Some(()))
         }
+        result
     }
 
     /// Some thread send a message by the channel. It goes to wait mode
     /// and all waiting receivers become sleeping and waiting for processor
-    /// time.
+    /// time. Threads that are blocked in `send` or `receive` on this channel
+    /// keep waiting for the queue.
     ///
     /// Returns array of threads that wake up from waiting state.
-    /// Error is returned if whether channel is not found or sender
-    /// is not found or not participating in the channel.
+    ///
+    /// # Errors
+    /// `UnknownThread` or `UnknownChannel` is returned if sender or channel
+    /// was not found. `NotParticipant` is returned if sender does not
+    /// participate in the channel. Errors of `wait_thread` are returned
+    /// if sender cannot wait for the channel. Woken threads remain awake
+    /// in that case.
     pub fn channel_signal(&mut self, sender: &ThreadKey,
-        channel: &ChannelKey, timer: bool
-    ) -> Result<LinkedList<ThreadKey>, ()> {
-        // Check whether this thread really is participating in given channel.
-        {
-            let chan = self.channels.get(channel);
-            if chan.is_none() {
-                return Err(());
+        channel: &ChannelKey, timeout: Option<Time>
+    ) -> Result<LinkedList<ThreadKey>, Error> {
+        self.check_participant(sender, channel)?;
+        let participants =
+                self.channels.get(channel).unwrap().participants().clone();
+
+        // List of all threads to wake up.
+        let mut list = LinkedList::new();
+
+        // Blocked senders and receivers wait for the queue, not for
+        // the signal.
+        let chan = self.channels.get(channel).unwrap();
+        let participants: Vec<_> = participants.into_iter()
+                .filter(|p| !chan.is_blocked(p))
+                .collect();
+
+        for participant_key in participants.iter() {
+            let thread = self.threads.get(participant_key).unwrap();
+            if thread.is_waiting_channel(channel) {
+                list.push_front(*participant_key);
+                self.wake_thread(participant_key,
+                        WakeReason::Signal(*channel))?;
             }
-            let chan = chan.unwrap();
+        }
 
-            let sender = chan.participants().get(sender);
-            if sender.is_none() {
-                return Err(());
+        // Set current thread to wait for signal from channel.
+        self.wait_thread(sender, channel, timeout)?;
+
+        Ok(list)
+    }
+
+    /// Send the message by the channel. If the queue of the channel is
+    /// full, sender goes to wait mode until the message is taken into the
+    /// queue by `receive`. Otherwise, the first receiver that waits for
+    /// a message becomes sleeping and waiting for processor time.
+    ///
+    /// # Errors
+    /// `UnknownThread` or `UnknownChannel` is returned if sender or channel
+    /// was not found. `NotParticipant` is returned if sender does not
+    /// participate in the channel. `Deadlock` is returned if sender cannot
+    /// wait for free space. Message is given back along with the error.
+    pub fn send(&mut self, sender: &ThreadKey, channel: &ChannelKey,
+        message: M
+    ) -> Result<Sent, (Error, M)> {
+        if let Err(e) = self.check_participant(sender, channel) {
+            return Err((e, message));
+        }
+
+        if self.channels.get(channel).unwrap().is_full() {
+            if let Err(e) = self.wait_thread(sender, channel, None) {
+                return Err((e, message));
             }
+            let chan = self.channels.get_mut(channel).unwrap();
+            chan.push_pending(*sender, message);
+            return Ok(Sent::Blocked);
         }
 
-        // List of all threads to wake up.
-        let mut list = LinkedList::new();
+        let chan = self.channels.get_mut(channel).unwrap();
+        chan.push(message);
 
-        for participant_key in
-                self.channels.get(channel).unwrap().participants().iter() {
-            let mut_self = unsafe { &mut *(self as *const _ as *mut Self) };
-            let thread = self.threads.get(&participant_key).unwrap();
-            if thread.is_waiting_channel(&channel) {
-                list.push_front(participant_key.clone());
-                mut_self.active_thread(&participant_key);
+        // Wake the first receiver which still waits for a message.
+        while let Some(receiver) = chan.pop_receiver() {
+            let thread = self.threads.get(&receiver).unwrap();
+            if thread.is_waiting_channel(channel) {
+                self.wake_thread(&receiver, WakeReason::Signal(*channel))
+                        .unwrap();
+                return Ok(Sent::Queued(Some(receiver)));
             }
         }
+        Ok(Sent::Queued(None))
+    }
 
-        // Set current thread to wait for signal from channel.
-        self.wait_thread(sender, channel, timer).unwrap();
+    /// Receive the message from the channel. If the queue of the channel is
+    /// empty, receiver goes to wait mode until some message is sent.
+    /// Otherwise, the first sender that waits for free space in the queue
+    /// becomes sleeping and waiting for processor time.
+    ///
+    /// # Errors
+    /// `UnknownThread` or `UnknownChannel` is returned if receiver or
+    /// channel was not found. `NotParticipant` is returned if receiver does
+    /// not participate in the channel. `Deadlock` is returned if receiver
+    /// cannot wait for a message.
+    pub fn receive(&mut self, receiver: &ThreadKey, channel: &ChannelKey)
+            -> Result<Received<M>, Error> {
+        self.check_participant(receiver, channel)?;
+
+        let chan = self.channels.get_mut(channel).unwrap();
+        match chan.pop() {
+            Some((message, sender)) => {
+                let woken = match sender {
+                    Some(sender) if self.threads.get(&sender).unwrap()
+                            .is_waiting_channel(channel) => {
+                        self.wake_thread(&sender,
+                                WakeReason::Signal(*channel))?;
+                        Some(sender)
+                    },
+                    _ => None,
+                };
+                Ok(Received::Message(message, woken))
+            },
+            None => {
+                self.wait_thread(receiver, channel, None)?;
+                let chan = self.channels.get_mut(channel).unwrap();
+                chan.push_receiver(*receiver);
+                Ok(Received::Blocked)
+            },
+        }
+    }
+
+    /// Close the channel and remove it from the network. All threads that
+    /// were waiting for a signal from this channel become sleeping and
+    /// waiting for processor time. Their wake reason is set to
+    /// `WakeReason::Closed`.
+    ///
+    /// # Returns
+    /// List of threads that woke up.
+    ///
+    /// # Errors
+    /// `UnknownChannel` is returned if channel was not found.
+    pub fn close_channel(&mut self, channel: &ChannelKey)
+            -> Result<LinkedList<ThreadKey>, Error> {
+        let participants =
+                self.existing_channel(channel)?.participants().clone();
+
+        let mut list = LinkedList::new();
+        for participant_key in participants.iter() {
+            let thread = self.threads.get(participant_key).unwrap();
+            if thread.is_waiting_channel(channel) {
+                list.push_back(*participant_key);
+                self.wake_thread(participant_key,
+                        WakeReason::Closed(*channel))?;
+            }
+
+            let thread = self.threads.get_mut(participant_key).unwrap();
+            thread.channels_mut().remove(channel);
+        }
+
+        self.drop_channel(channel);
 
         Ok(list)
     }
@@ -274,20 +953,130 @@ impl Network {
         self.threads.get(thread)
     }
 
+    /// Check that policies give the access to the interfaces at the target
+    /// path to the process at given path.
+    fn check_access(&self, target: &RcPath, access: Access, process: &RcPath)
+            -> Result<(), Error> {
+        if self.packages.permits(target, access, process) {
+            Ok(())
+        } else {
+            Err(Error::PermissionDenied(process.clone(), access,
+                    target.clone()))
+        }
+    }
+
+    /// Put waiting thread asleep and save the reason why it woke up.
+    fn wake_thread(&mut self, thread: &ThreadKey, reason: WakeReason)
+            -> Result<(), Error> {
+        self.sleep_thread(thread)?;
+        self.thread_mut(thread).unwrap().set_wake_reason(reason);
+        Ok(())
+    }
+
+    /// Check that both thread and channel exist and that the thread
+    /// participates in the channel.
+    fn check_participant(&self, thread: &ThreadKey, channel: &ChannelKey)
+            -> Result<(), Error> {
+        self.existing_thread(thread)?;
+        if !self.existing_channel(channel)?.participants().contains(thread) {
+            return Err(Error::NotParticipant(*thread, *channel));
+        }
+        Ok(())
+    }
+
+    /// Process of the thread with it's key.
+    ///
+    /// # Errors
+    /// `UnknownThread` is returned if thread was not found.
+    /// `ThreadHasNoProcess` is returned if no process owns the thread.
+    fn process_of(&self, thread: &ThreadKey)
+            -> Result<(ProcessKey, &Process), Error> {
+        self.existing_thread(thread)?;
+        match self.processes.processes().iter()
+                .find(|(_, p)| p.threads().contains(thread)) {
+            Some((key, process)) => Ok((*key, process)),
+            None => Err(Error::ThreadHasNoProcess(*thread)),
+        }
+    }
+
+    /// Thread by the key or `UnknownThread` error if it was not found.
+    fn existing_thread(&self, thread: &ThreadKey) -> Result<&Thread, Error> {
+        match self.threads.get(thread) {
+            Some(t) => Ok(t),
+            None => Err(Error::UnknownThread(*thread)),
+        }
+    }
+
+    /// Channel by the key or `UnknownChannel` error if it was not found.
+    fn existing_channel(&self, channel: &ChannelKey)
+            -> Result<&Channel<M>, Error> {
+        match self.channels.get(channel) {
+            Some(c) => Ok(c),
+            None => Err(Error::UnknownChannel(*channel)),
+        }
+    }
+
+    /// Register relations of the channel to the channels where it's
+    /// participants wait for a signal. Relations are only created when
+    /// all participants are locked, as otherwise any of the active ones
+    /// can still signal the channel.
+    ///
+    /// Participant that waits for the same channel cannot signal it and so
+    /// it creates no relation, unless all of the participants wait for it.
+    fn add_channel_relations(&mut self, channel: &ChannelKey)
+            -> Result<(), Error> {
+        for (participant, source) in self.lock_relations(channel) {
+            self.wait_deps.add_channel_relation(&source, channel,
+                    &participant)?;
+        }
+        Ok(())
+    }
+
+    /// Register relations of the channel like `add_channel_relations` does
+    /// but without rejecting the ones that form a deadlock. Used when the
+    /// network gets into the deadlock by other means than waiting.
+    fn restore_channel_relations(&mut self, channel: &ChannelKey) {
+        for (participant, source) in self.lock_relations(channel) {
+            self.wait_deps.add_channel_relation_unchecked(&source, channel,
+                    &participant).unwrap();
+        }
+    }
+
+    /// Participants of the locked channel with the channels where they
+    /// wait for a signal. Empty if the channel is not locked.
+    fn lock_relations(&self, channel: &ChannelKey)
+            -> BTreeMap<ThreadKey, ChannelKey> {
+        let participants = self.channels.get(channel).unwrap().participants();
+
+        // Channels where participants wait for a signal.
+        let mut sources = BTreeMap::new();
+        for participant in participants.iter() {
+            let thread = self.threads.get(participant).unwrap();
+            match thread.state() {
+                ThreadState::WaitWithoutTimeout(source) =>
+                    sources.insert(*participant, *source),
+                _ => return BTreeMap::new(),
+            };
+        }
+
+        let all_same = sources.values().all(|source| source == channel);
+        sources.retain(|_, source| all_same || source != channel);
+        sources
+    }
+
     /// Change thread state to given and remove thread from wait dependency.
     fn change_thread_state_remove_deps(&mut self, thread: &ThreadKey,
-            state: ThreadState) -> Option<()> {
-        let old_state = {
-            let thread = self.thread_mut(thread);
-            if thread.is_none() {
-                return None;
-            }
-            let thread = thread.unwrap();
+            state: ThreadState) -> Result<(), Error> {
+        self.existing_thread(thread)?;
+        let mut affected = self.lent_to(thread);
 
-            let old_state = thread.state().clone();
+        let old_state = {
+            let thread = self.thread_mut(thread).unwrap();
+            let old_state = *thread.state();
             thread.set_state(state);
             old_state
         };
+        self.update_run_queue(thread);
 
         use std::mem::discriminant;
         let without_timeout = discriminant(&ThreadState::WaitWithoutTimeout(0));
@@ -296,12 +1085,155 @@ impl Network {
             self.remove_from_wait_dep(thread);
         }
 
-        Some(())
+        affected.append(&mut self.lent_to(thread));
+        affected.insert(*thread);
+        self.inherit_priorities(&affected);
+
+        Ok(())
+    }
+
+    /// Put thread in the run queue or on the processor according to it's
+    /// state.
+    fn update_run_queue(&mut self, thread: &ThreadKey) {
+        match self.threads.get(thread).unwrap().state() {
+            ThreadState::Sleep => {
+                self.running.remove(thread);
+                self.scheduler.push(*thread);
+            },
+            ThreadState::Active => {
+                self.scheduler.remove(thread);
+                self.running.insert(*thread);
+            },
+            _ => {
+                self.scheduler.remove(thread);
+                self.running.remove(thread);
+            },
+        }
     }
 
-    /// Remove process from wait dependency.
+    /// Recompute priorities that given threads inherit. Thread that
+    /// waits for a signal without timeout lends it's priority to the other
+    /// participants of the channel, as they are the ones that can signal
+    /// it. If such participant waits for some channel itself, the priority
+    /// is passed further along the chain.
+    ///
+    /// Threads that wait with timeout lend no priority, as they are not
+    /// locked and wake up by themselves when the timeout expires.
+    ///
+    /// Scheduler is notified about the threads which effective priority
+    /// has changed.
+    fn inherit_priorities(&mut self, threads: &BTreeSet<ThreadKey>) {
+        for key in threads.iter() {
+            if self.threads.get(key).is_none() {
+                continue;
+            }
+
+            let inherited = self.inherited_priority(key);
+            let thread = self.threads.get_mut(key).unwrap();
+            let old = thread.effective_priority();
+            thread.set_inherited_priority(inherited);
+            let new = thread.effective_priority();
+            if old != new {
+                self.scheduler.set_priority(key, new);
+            }
+        }
+    }
+
+    /// Greatest priority of the threads that lend it to given thread
+    /// directly or along the chain. None if no thread lends it's priority.
+    fn inherited_priority(&self, thread: &ThreadKey) -> Option<Priority> {
+        let mut inherited = None;
+        let mut visited = BTreeSet::new();
+        visited.insert(*thread);
+        let mut stack = vec![*thread];
+
+        while let Some(key) = stack.pop() {
+            let receiver = self.threads.get(&key).unwrap();
+            for chan in receiver.channels() {
+                if receiver.is_waiting_channel(chan) {
+                    // Cannot signal the channel.
+                    continue;
+                }
+
+                let channel = self.channels.get(chan).unwrap();
+                for participant in channel.participants() {
+                    let lender = self.threads.get(participant).unwrap();
+                    let waits = ThreadState::WaitWithoutTimeout(*chan);
+                    if *lender.state() != waits
+                            || !visited.insert(*participant) {
+                        continue;
+                    }
+
+                    inherited = inherited.max(Some(lender.priority()));
+                    stack.push(*participant);
+                }
+            }
+        }
+        inherited
+    }
+
+    /// Threads that given thread lends it's priority to directly or along
+    /// the chain. These are the threads which inherited priority may
+    /// change when the priority or the state of given thread changes.
+    fn lent_to(&self, thread: &ThreadKey) -> BTreeSet<ThreadKey> {
+        let mut lent = BTreeSet::new();
+        let mut stack = vec![*thread];
+
+        while let Some(key) = stack.pop() {
+            let source = match self.threads.get(&key).unwrap().state() {
+                ThreadState::WaitWithoutTimeout(source) => *source,
+                _ => continue,
+            };
+
+            let channel = self.channels.get(&source).unwrap();
+            for participant in channel.participants() {
+                let receiver = self.threads.get(participant).unwrap();
+                if participant == thread
+                        || receiver.is_waiting_channel(&source) {
+                    // Cannot signal the channel.
+                    continue;
+                }
+                if lent.insert(*participant) {
+                    stack.push(*participant);
+                }
+            }
+        }
+        lent
+    }
+
+    /// Remove the channel from the wait map and from the network. Channel
+    /// must have no waiters left.
+    fn drop_channel(&mut self, channel: &ChannelKey) {
+        let removed = self.wait_deps.remove_channel(channel)
+                .expect("channels of the network are in the wait map");
+        assert!(removed, "channel {} still has waiters", channel);
+        self.channels.remove(*channel);
+    }
+
+    /// Remove thread from wait dependency.
     fn remove_from_wait_dep(&mut self, thread: &ThreadKey) {
         self.wait_deps.remove_thread(thread);
+
+        // Channels of this thread are not locked by it anymore and so
+        // they no longer depend on any other channel.
+        let channels = self.threads.get(thread).unwrap().channels();
+        for ch in channels.iter() {
+            self.wait_deps.remove_channel_relations(ch).unwrap();
+        }
+    }
+}
+
+impl InterfaceRemoval {
+
+    /// Removed interfaces. These are the requested interface and, for the
+    /// cascade removal, interfaces that required it.
+    pub fn interfaces(&self) -> &BTreeMap<InterfaceKey, Interface> {
+        &self.interfaces
+    }
+
+    /// Processes that lost implementations of removed interfaces.
+    pub fn processes(&self) -> &BTreeSet<ProcessKey> {
+        &self.processes
     }
 }
 
@@ -315,8 +1247,8 @@ mod tests {
         let proc_path2 = Path::new("b".to_string());
 
         let mut network = Network::new();
-        let proc1 = network.new_process(Process::new(proc_path1));
-        let proc2 = network.new_process(Process::new(proc_path2));
+        let proc1 = network.new_process(Process::new(proc_path1)).unwrap();
+        let proc2 = network.new_process(Process::new(proc_path2)).unwrap();
 
         let th1 = network.new_thread(Thread::new(), &proc1).unwrap();
         let th2 = network.new_thread(Thread::new(), &proc1).unwrap();
@@ -335,9 +1267,59 @@ mod tests {
         let ch23 = network.new_channel(ch23).unwrap();
         let ch31 = network.new_channel(ch31).unwrap();
 
-        assert!(network.wait_thread(&th1, &ch12, false).is_ok());
-        //assert!(network.wait_thread(&th2, &ch23, false).is_ok());
-        //assert!(network.wait_thread(&th3, &ch31, false).is_err());
+        assert!(network.wait_thread(&th1, &ch12, None).is_ok());
+        assert!(network.wait_thread(&th2, &ch23, None).is_ok());
+
+        let cycle = match network.wait_thread(&th3, &ch31, None) {
+            Err(Error::Deadlock(cycle)) => cycle,
+            _ => panic!("deadlock was not detected"),
+        };
+        assert_eq!(cycle.channels(), &[ch31, ch12, ch23]);
+        assert!(cycle.causes()[0].contains(&th1));
+        assert!(cycle.causes()[1].contains(&th2));
+        assert!(cycle.causes()[2].contains(&th3));
+
+        // Refused wait is reverted.
+        assert!(!network.thread(&th3).unwrap().is_waiting_channel(&ch31));
+        assert!(network.wait_deps().relation_causes(&ch31, &ch23).is_none());
+        assert!(network.wait_deps().relation_causes(&ch23, &ch12).is_some());
+    }
+
+    #[test]
+    fn network_wait_refused_keeps_previous() {
+        let mut network = Network::new();
+        let proc1 = network.new_process(Process::new(Path::new("a".to_string())))
+                .unwrap();
+
+        let th1 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th2 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th3 = network.new_thread(Thread::new(), &proc1).unwrap();
+
+        let mut ch12 = Channel::new(th1);
+        ch12.add_participant(th2);
+        let mut ch13 = Channel::new(th1);
+        ch13.add_participant(th3);
+
+        let ch12 = network.new_channel(ch12).unwrap();
+        let ch13 = network.new_channel(ch13).unwrap();
+
+        network.wait_thread(&th1, &ch13, None).unwrap();
+        network.wait_thread(&th2, &ch12, None).unwrap();
+        assert!(network.wait_deps().relation_causes(&ch13, &ch12).is_some());
+
+        // Both participants of the channel would wait for it.
+        match network.wait_thread(&th1, &ch12, None) {
+            Err(Error::Deadlock(_)) => (),
+            _ => panic!("deadlock was not detected"),
+        }
+
+        let thread = network.thread(&th1).unwrap();
+        assert!(*thread.state() == ThreadState::WaitWithoutTimeout(ch13));
+        assert!(network.wait_deps().thread_wait_map()[&th1].contains(&ch13));
+        assert!(network.wait_deps().channel_wait_map()[&ch13].contains(&th1));
+        assert!(network.wait_deps().relation_causes(&ch13, &ch12).unwrap()
+                .contains(&th1));
+        assert!(network.wait_deps().relation_causes(&ch12, &ch12).is_none());
     }
 
     #[test]
@@ -346,8 +1328,8 @@ mod tests {
         let proc_path2 = Path::new("b".to_string());
 
         let mut network = Network::new();
-        let proc1 = network.new_process(Process::new(proc_path1));
-        let proc2 = network.new_process(Process::new(proc_path2));
+        let proc1 = network.new_process(Process::new(proc_path1)).unwrap();
+        let proc2 = network.new_process(Process::new(proc_path2)).unwrap();
 
         let th1 = network.new_thread(Thread::new(), &proc1).unwrap();
         let th2 = network.new_thread(Thread::new(), &proc1).unwrap();
@@ -377,4 +1359,643 @@ mod tests {
         assert!(network.channels.get(&ch23).is_some());
         assert!(network.channels.get(&ch31).is_some());
     }
+
+    #[test]
+    fn network_remove_thread() {
+        let mut network = Network::new();
+        let proc1 = network.new_process(Process::new(Path::new("a".to_string())))
+                .unwrap();
+
+        let th1 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th2 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th3 = network.new_thread(Thread::new(), &proc1).unwrap();
+
+        let mut ch12 = Channel::new(th1);
+        ch12.add_participant(th2);
+        let mut ch13 = Channel::new(th1);
+        ch13.add_participant(th3);
+
+        let ch12 = network.new_channel(ch12).unwrap();
+        let ch13 = network.new_channel(ch13).unwrap();
+
+        network.wait_thread(&th2, &ch12, None).unwrap();
+        network.wait_thread(&th3, &ch13, None).unwrap();
+
+        let blocked = network.remove_thread(&th1).unwrap();
+        assert!(blocked.contains(&th2));
+        assert!(blocked.contains(&th3));
+        assert_eq!(blocked.len(), 2);
+
+        assert!(network.thread(&th1).is_none());
+        assert!(!network.processes().get(&proc1).unwrap().threads()
+                .contains(&th1));
+        assert!(!network.channels().get(&ch12).unwrap().participants()
+                .contains(&th1));
+        assert!(!network.channels().get(&ch13).unwrap().participants()
+                .contains(&th1));
+        assert!(network.wait_deps().thread_wait_map().get(&th1).is_none());
+
+        assert_eq!(network.remove_thread(&th1), Err(Error::UnknownThread(th1)));
+    }
+
+    #[test]
+    fn network_remove_thread_relations() {
+        let mut network = Network::new();
+        let proc1 = network.new_process(Process::new(Path::new("a".to_string())))
+                .unwrap();
+
+        let th1 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th2 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th3 = network.new_thread(Thread::new(), &proc1).unwrap();
+
+        let mut ch123 = Channel::new(th1);
+        ch123.add_participant(th2);
+        ch123.add_participant(th3);
+        let mut ch23 = Channel::new(th2);
+        ch23.add_participant(th3);
+
+        let ch1 = network.new_channel(Channel::new(th1)).unwrap();
+        let ch123 = network.new_channel(ch123).unwrap();
+        let ch23 = network.new_channel(ch23).unwrap();
+
+        network.wait_thread(&th2, &ch23, None).unwrap();
+        network.wait_thread(&th3, &ch123, None).unwrap();
+        assert!(network.wait_deps().analyze().is_clean());
+
+        // Only the removed thread could signal the channels, so the rest
+        // now wait for each other.
+        let blocked = network.remove_thread(&th1).unwrap();
+        assert_eq!(blocked.len(), 1);
+        assert!(blocked.contains(&th3));
+        assert!(network.channels().get(&ch1).is_none());
+        assert!(network.wait_deps().relation_causes(&ch23, &ch123)
+                .unwrap().contains(&th2));
+
+        let analysis = network.wait_deps().analyze();
+        assert_eq!(analysis.deadlocks().len(), 1);
+        let deadlock = &analysis.deadlocks()[0];
+        assert!(deadlock.channels().contains(&ch123));
+        assert!(deadlock.channels().contains(&ch23));
+    }
+
+    #[test]
+    fn network_remove_process() {
+        let mut network = Network::new();
+        let path = Path::new("a".to_string());
+        let proc1 = network.new_process(Process::new(path.clone())).unwrap();
+        let proc2 = network.new_process(Process::new(path.clone())).unwrap();
+        let proc3 = network.new_process(Process::new(Path::new("b".to_string())))
+                .unwrap();
+
+        let th1 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th2 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th3 = network.new_thread(Thread::new(), &proc2).unwrap();
+        let th4 = network.new_thread(Thread::new(), &proc3).unwrap();
+
+        let mut ch12 = Channel::new(th1);
+        ch12.add_participant(th2);
+        let mut ch13 = Channel::new(th1);
+        ch13.add_participant(th3);
+
+        let ch12 = network.new_channel(ch12).unwrap();
+        let ch13 = network.new_channel(ch13).unwrap();
+
+        network.wait_thread(&th2, &ch12, None).unwrap();
+        network.wait_thread(&th3, &ch13, None).unwrap();
+
+        let removal = network.remove_process(&proc1).unwrap();
+        assert!(removal.threads().contains(&th1));
+        assert!(removal.threads().contains(&th2));
+        assert!(removal.blocked().contains(&th3));
+        assert_eq!(removal.blocked().len(), 1);
+        assert!(!removal.package_removed());
+
+        assert!(network.processes().get(&proc1).is_none());
+        assert!(network.thread(&th1).is_none());
+        assert!(network.thread(&th2).is_none());
+        assert!(network.channels().get(&ch12).is_none());
+
+        // Last process in the package.
+        let removal = network.remove_process(&proc2).unwrap();
+        assert!(removal.package_removed());
+        assert!(network.thread(&th4).is_some());
+
+        assert!(network.remove_process(&proc1).is_err());
+    }
+
+    #[test]
+    fn network_close_channel() {
+        let mut network = Network::new();
+        let proc1 = network.new_process(Process::new(Path::new("a".to_string())))
+                .unwrap();
+
+        let th1 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th2 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th3 = network.new_thread(Thread::new(), &proc1).unwrap();
+
+        let mut ch12 = Channel::new(th1);
+        ch12.add_participant(th2);
+        let mut ch23 = Channel::new(th2);
+        ch23.add_participant(th3);
+
+        let ch12 = network.new_channel(ch12).unwrap();
+        let ch23 = network.new_channel(ch23).unwrap();
+
+        network.wait_thread(&th1, &ch12, None).unwrap();
+        network.wait_thread(&th3, &ch23, None).unwrap();
+
+        let woken = network.close_channel(&ch12).unwrap();
+        assert_eq!(woken.len(), 1);
+        assert!(woken.contains(&th1));
+
+        let thread = network.thread(&th1).unwrap();
+        assert!(*thread.state() == ThreadState::Sleep);
+        assert_eq!(thread.wake_reason(), Some(&WakeReason::Closed(ch12)));
+        assert!(!thread.channels().contains(&ch12));
+        assert!(!network.thread(&th2).unwrap().channels().contains(&ch12));
+
+        // Waiter of other channel is not affected.
+        assert!(network.thread(&th3).unwrap().is_waiting_channel(&ch23));
+
+        assert!(network.channels().get(&ch12).is_none());
+        assert!(network.wait_deps().channel_wait_map().get(&ch12).is_none());
+        assert_eq!(network.close_channel(&ch12),
+                Err(Error::UnknownChannel(ch12)));
+    }
+
+    #[test]
+    fn network_errors() {
+        let mut network = Network::new();
+        let proc1 = network.new_process(Process::new(Path::new("a".to_string())))
+                .unwrap();
+
+        assert_eq!(network.new_thread(Thread::new(), &(proc1 + 1)),
+                Err(Error::UnknownProcess(proc1 + 1)));
+        let mut attached = Process::new(Path::new("b".to_string()));
+        attached.attach_thread(42);
+        assert_eq!(network.new_process(attached).err(),
+                Some(Error::ProcessHasThreads));
+
+        let th1 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th2 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th3 = network.new_thread(Thread::new(), &proc1).unwrap();
+
+        let mut ch12 = Channel::new(th1);
+        ch12.add_participant(th2);
+        let ch12 = network.new_channel(ch12).unwrap();
+
+        assert_eq!(network.new_channel(Channel::new(th3 + 1)),
+                Err(Error::UnknownThread(th3 + 1)));
+        assert_eq!(network.wait_thread(&th1, &(ch12 + 1), None),
+                Err(Error::UnknownChannel(ch12 + 1)));
+        assert_eq!(network.wait_thread(&th3, &ch12, None),
+                Err(Error::NotParticipant(th3, ch12)));
+        assert_eq!(network.channel_signal(&th3, &ch12, None),
+                Err(Error::NotParticipant(th3, ch12)));
+        assert_eq!(network.channel_signal(&(th3 + 1), &ch12, None),
+                Err(Error::UnknownThread(th3 + 1)));
+    }
+
+    #[test]
+    fn network_wait_timeout() {
+        let mut network = Network::new();
+        let proc1 = network.new_process(Process::new(Path::new("a".to_string())))
+                .unwrap();
+
+        let th1 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th2 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th3 = network.new_thread(Thread::new(), &proc1).unwrap();
+
+        let mut ch = Channel::new(th1);
+        ch.add_participant(th2);
+        ch.add_participant(th3);
+        let ch = network.new_channel(ch).unwrap();
+
+        network.advance_time(10);
+        network.wait_thread(&th1, &ch, Some(5)).unwrap();
+        network.wait_thread(&th2, &ch, Some(8)).unwrap();
+        assert!(*network.thread(&th1).unwrap().state()
+                == ThreadState::WaitWithTimeout(ch, 15));
+
+        assert!(network.advance_time(4).is_empty());
+
+        let woken = network.advance_time(1);
+        assert_eq!(woken.len(), 1);
+        assert!(woken.contains(&th1));
+        let thread = network.thread(&th1).unwrap();
+        assert!(*thread.state() == ThreadState::Sleep);
+        assert_eq!(thread.wake_reason(), Some(&WakeReason::TimedOut(ch)));
+
+        // Signal received before timeout expires.
+        let woken = network.channel_signal(&th3, &ch, None).unwrap();
+        assert!(woken.contains(&th2));
+        assert!(network.advance_time(10).is_empty());
+        assert_eq!(network.thread(&th2).unwrap().wake_reason(),
+                Some(&WakeReason::Signal(ch)));
+        assert_eq!(network.clock().now(), 25);
+    }
+
+    #[test]
+    fn network_send_receive() {
+        let mut network: Network<&str> = Network::default();
+        let proc1 = network.new_process(Process::new(Path::new("a".to_string())))
+                .unwrap();
+
+        let th1 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th2 = network.new_thread(Thread::new(), &proc1).unwrap();
+
+        let mut ch = Channel::with_capacity(th1, 1);
+        ch.add_participant(th2);
+        let ch = network.new_channel(ch).unwrap();
+
+        assert_eq!(network.receive(&th2, &ch), Ok(Received::Blocked));
+        assert!(network.thread(&th2).unwrap().is_waiting_channel(&ch));
+
+        assert_eq!(network.send(&th1, &ch, "a"), Ok(Sent::Queued(Some(th2))));
+        assert!(*network.thread(&th2).unwrap().state() == ThreadState::Sleep);
+
+        assert_eq!(network.send(&th1, &ch, "b"), Ok(Sent::Blocked));
+        assert!(network.thread(&th1).unwrap().is_waiting_channel(&ch));
+
+        assert_eq!(network.receive(&th2, &ch),
+                Ok(Received::Message("a", Some(th1))));
+        assert!(*network.thread(&th1).unwrap().state() == ThreadState::Sleep);
+        assert_eq!(network.receive(&th2, &ch), Ok(Received::Message("b", None)));
+
+        // Both threads wait for a message that nobody can send.
+        assert_eq!(network.receive(&th2, &ch), Ok(Received::Blocked));
+        match network.receive(&th1, &ch) {
+            Err(Error::Deadlock(cycle)) => assert_eq!(cycle.channels(), &[ch]),
+            other => panic!("deadlock was not detected: {:?}", other),
+        }
+
+        // Refused message is given back to the sender.
+        assert_eq!(network.send(&th1, &ch, "c"), Ok(Sent::Queued(Some(th2))));
+        network.wait_thread(&th2, &ch, None).unwrap();
+        match network.send(&th1, &ch, "d") {
+            Err((Error::Deadlock(_), message)) => assert_eq!(message, "d"),
+            other => panic!("deadlock was not detected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn network_signal_full_channel() {
+        let mut network: Network<&str> = Network::default();
+        let proc1 = network.new_process(Process::new(Path::new("a".to_string())))
+                .unwrap();
+
+        let th1 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th2 = network.new_thread(Thread::new(), &proc1).unwrap();
+
+        let mut ch = Channel::with_capacity(th1, 1);
+        ch.add_participant(th2);
+        let ch = network.new_channel(ch).unwrap();
+
+        assert_eq!(network.send(&th1, &ch, "a"), Ok(Sent::Queued(None)));
+        assert_eq!(network.send(&th1, &ch, "b"), Ok(Sent::Blocked));
+
+        // Blocked sender keeps waiting for free space.
+        assert!(network.channel_signal(&th2, &ch, Some(10)).unwrap()
+                .is_empty());
+        assert!(network.thread(&th1).unwrap().is_waiting_channel(&ch));
+        assert!(network.channels().get(&ch).unwrap().is_blocked(&th1));
+
+        assert_eq!(network.receive(&th2, &ch),
+                Ok(Received::Message("a", Some(th1))));
+        assert!(*network.thread(&th1).unwrap().state() == ThreadState::Sleep);
+    }
+
+    #[test]
+    fn network_connect() {
+        let mut network = Network::new();
+        let path: RcPath = "a.b".parse().unwrap();
+        let key = |version: &str| {
+            InterfaceKey::new(path.clone(), version.parse().unwrap())
+        };
+
+        network.add_interface(key("1.2.0"), Interface::new()).unwrap();
+        network.add_interface(key("1.4.0"), Interface::new()).unwrap();
+
+        let client_proc = network.new_process(
+                Process::new("c".parse().unwrap())).unwrap();
+        let client = network.new_thread(Thread::new(), &client_proc).unwrap();
+
+        let mut old = Process::new(path.clone());
+        old.add_implementation(key("1.2.0"));
+        let old = network.new_process(old).unwrap();
+        let old_thread = network.new_thread(Thread::new(), &old).unwrap();
+
+        let mut new = Process::new(path.clone());
+        new.add_implementation(key("1.4.0"));
+        let new = network.new_process(new).unwrap();
+        let th1 = network.new_thread(Thread::new(), &new).unwrap();
+        let th2 = network.new_thread(Thread::new(), &new).unwrap();
+
+        // Highest version wins and threads are balanced.
+        let req: VersionReq = "^1.2".parse().unwrap();
+        let ch1 = network.connect(&client, &path, &req).unwrap();
+        let ch2 = network.connect(&client, &path, &req).unwrap();
+        let participants = network.channels().get(&ch1).unwrap().participants();
+        assert!(participants.contains(&client) && participants.contains(&th1));
+        assert!(network.channels().get(&ch2).unwrap().participants()
+                .contains(&th2));
+
+        let ch = network.connect_key(&client, &key("1.2.0")).unwrap();
+        assert!(network.channels().get(&ch).unwrap().participants()
+                .contains(&old_thread));
+
+        let req: VersionReq = "^2".parse().unwrap();
+        assert_eq!(network.connect(&client, &path, &req),
+                Err(Error::NoImplementer(path.clone(), req)));
+        assert_eq!(network.connect_key(&(th2 + 1), &key("1.2.0")),
+                Err(Error::UnknownThread(th2 + 1)));
+
+        // Implementer does not connect to itself, but to the other thread
+        // of it's process.
+        assert!(network.connect_key(&old_thread, &key("1.2.0")).is_err());
+        let ch = network.connect_key(&th1, &key("1.4.0")).unwrap();
+        assert!(network.channels().get(&ch).unwrap().participants()
+                .contains(&th2));
+
+        // Implementations of unregistered interfaces are not used.
+        let mut rogue = Process::new(path.clone());
+        rogue.add_implementation(key("1.9.0"));
+        let rogue = network.new_process(rogue).unwrap();
+        network.new_thread(Thread::new(), &rogue).unwrap();
+        let req: VersionReq = "^1.9".parse().unwrap();
+        assert_eq!(network.connect(&client, &path, &req),
+                Err(Error::NoImplementer(path.clone(), req)));
+    }
+
+    #[test]
+    fn network_remove_interface() {
+        let mut network = Network::new();
+        let key = |s: &str| -> InterfaceKey { s.parse().unwrap() };
+
+        let mut a = Interface::new();
+        a.add_prerequisite(key("b@1.0.0"));
+        network.add_interface(key("a@1.0.0"), a).unwrap();
+        network.add_interface(key("b@1.0.0"), Interface::new()).unwrap();
+
+        let mut process = Process::new("p".parse().unwrap());
+        process.add_implementation(key("a@1.0.0"));
+        process.add_implementation(key("b@1.0.0"));
+        let process = network.new_process(process).unwrap();
+
+        let err = network.remove_interface(&key("b@1.0.0")).err().unwrap();
+        assert_eq!(err, Error::InterfaceInUse(key("b@1.0.0"),
+                vec![key("a@1.0.0")].into_iter().collect(),
+                vec![process].into_iter().collect()));
+        assert_eq!(err.to_string(), "interface b@1.0.0 is in use, \
+                required by a@1.0.0, implemented by process 0");
+
+        let entries = network.packages().entries(&"p".parse().unwrap())
+                .unwrap();
+        assert!(entries.processes().contains(&process));
+        assert!(network.packages().entries(&"a".parse().unwrap()).unwrap()
+                .interfaces().contains(&key("a@1.0.0")));
+
+        let removal = network.remove_interface_cascade(&key("b@1.0.0"))
+                .unwrap();
+        assert!(!network.packages().exists(&"a".parse().unwrap()));
+        assert_eq!(removal.interfaces().len(), 2);
+        assert!(removal.processes().contains(&process));
+        assert!(network.interfaces().interfaces().is_empty());
+        assert!(network.processes().get(&process).unwrap()
+                .implementations().is_empty());
+
+        network.add_interface(key("c@1.0.0"), Interface::new()).unwrap();
+        network.remove_interface(&key("c@1.0.0")).unwrap();
+        assert_eq!(network.remove_interface(&key("c@1.0.0")).err(),
+                Some(Error::UnknownInterface(key("c@1.0.0"))));
+    }
+
+    #[test]
+    fn network_policy() {
+        let mut network = Network::new();
+        let path = |s: &str| -> RcPath { s.parse().unwrap() };
+        let key: InterfaceKey = "sys.fs.File@1.0.0".parse().unwrap();
+        network.add_interface(key.clone(), Interface::new()).unwrap();
+
+        let mut policy = Policy::new();
+        policy.allow(Access::Implement, path("sys"));
+        policy.allow(Access::Connect, path("usr.trusted"));
+        network.set_policy(&path("sys"), policy);
+
+        let mut rogue = Process::new(path("usr.rogue"));
+        rogue.add_implementation(key.clone());
+        assert_eq!(network.new_process(rogue).err(),
+                Some(Error::PermissionDenied(path("usr.rogue"),
+                        Access::Implement, path("sys.fs.File"))));
+        let rogue = network.new_process(Process::new(path("usr.rogue")))
+                .unwrap();
+        let err = network.add_implementation(&rogue, key.clone()).unwrap_err();
+        assert_eq!(err.to_string(), "process at usr.rogue may not implement \
+                interfaces at sys.fs.File");
+
+        let fs = network.new_process(Process::new(path("sys.fs"))).unwrap();
+        assert_eq!(network.add_implementation(&fs, key.clone()), Ok(true));
+        let open = InterfaceFunc::new("open".to_string(), Version::new(1, 0, 0));
+        assert_eq!(network.add_fn_implementation(&fs, key.clone(),
+                open.clone()), Ok(true));
+        assert!(network.processes().get(&fs).unwrap()
+                .fn_implementations(&key).unwrap().contains(&open));
+        assert_eq!(network.add_fn_implementation(&rogue, key.clone(), open),
+                Err(Error::PermissionDenied(path("usr.rogue"),
+                        Access::Implement, path("sys.fs.File"))));
+        let server = network.new_thread(Thread::new(), &fs).unwrap();
+
+        let trusted = network.new_process(Process::new(path("usr.trusted")))
+                .unwrap();
+        let client = network.new_thread(Thread::new(), &trusted).unwrap();
+        assert!(network.connect_key(&client, &key).is_ok());
+
+        let client = network.new_thread(Thread::new(), &rogue).unwrap();
+        assert_eq!(network.connect_key(&client, &key),
+                Err(Error::PermissionDenied(path("usr.rogue"),
+                        Access::Connect, path("sys.fs.File"))));
+
+        // Channels created directly are subject to the same policy.
+        let mut channel = Channel::new(client);
+        channel.add_participant(server);
+        assert_eq!(network.new_channel(channel),
+                Err(Error::PermissionDenied(path("usr.rogue"),
+                        Access::Connect, path("sys.fs.File"))));
+        let rogue_peer = network.new_thread(Thread::new(), &rogue).unwrap();
+        let mut channel = Channel::new(client);
+        channel.add_participant(rogue_peer);
+        assert!(network.new_channel(channel).is_ok());
+
+        // Other process in the same package is checked as well.
+        let twin = network.new_process(Process::new(path("sys.fs"))).unwrap();
+        let twin = network.new_thread(Thread::new(), &twin).unwrap();
+        let mut channel = Channel::new(twin);
+        channel.add_participant(server);
+        assert_eq!(network.new_channel(channel),
+                Err(Error::PermissionDenied(path("sys.fs"),
+                        Access::Connect, path("sys.fs.File"))));
+
+        // Thread outside of processes cannot be checked and is refused.
+        let orphan = network.threads.add(Thread::new());
+        assert_eq!(network.connect_key(&orphan, &key),
+                Err(Error::ThreadHasNoProcess(orphan)));
+
+        // Sub-package policy overrides the inherited one.
+        network.set_policy(&path("sys.fs"), Policy::new());
+        assert!(network.connect_key(&client, &key).is_ok());
+        assert!(network.remove_policy(&path("sys.fs")).is_some());
+        assert!(network.connect_key(&client, &key).is_err());
+    }
+
+    #[test]
+    fn network_policy_channel() {
+        let mut network = Network::new();
+        let path = |s: &str| -> RcPath { s.parse().unwrap() };
+        let key = |s: &str| -> InterfaceKey { s.parse().unwrap() };
+
+        network.add_interface(key("srv.a.A@1.0.0"), Interface::new()).unwrap();
+        network.add_interface(key("srv.b.B@1.0.0"), Interface::new()).unwrap();
+
+        let mut server = Process::new(path("srv"));
+        server.add_implementation(key("srv.a.A@1.0.0"));
+        server.add_implementation(key("srv.b.B@1.0.0"));
+        let server = network.new_process(server).unwrap();
+        let server = network.new_thread(Thread::new(), &server).unwrap();
+
+        let mut client = Process::new(path("usr.client"));
+        client.add_implementation(key("usr.client.C@1.0.0"));
+        let client = network.new_process(client).unwrap();
+        let client = network.new_thread(Thread::new(), &client).unwrap();
+
+        // Client may use only A, nobody may use the interface of the client.
+        let mut policy = Policy::new();
+        policy.allow(Access::Connect, path("usr.trusted"));
+        network.set_policy(&path("srv.b"), policy.clone());
+        network.set_policy(&path("usr.client"), policy);
+
+        assert!(network.connect_key(&client, &key("srv.b.B@1.0.0")).is_err());
+        assert!(network.connect_key(&client, &key("srv.a.A@1.0.0")).is_ok());
+
+        // Only the creator is checked, against any of the interfaces.
+        let mut channel = Channel::new(client);
+        channel.add_participant(server);
+        assert!(network.new_channel(channel).is_ok());
+        let mut channel = Channel::new(server);
+        channel.add_participant(client);
+        assert_eq!(network.new_channel(channel),
+                Err(Error::PermissionDenied(path("srv"), Access::Connect,
+                        path("usr.client.C"))));
+    }
+
+    #[test]
+    fn network_schedule() {
+        let mut network = Network::new();
+        network.set_cpu_count(2);
+        let proc1 = network.new_process(Process::new(Path::new("a".to_string())))
+                .unwrap();
+
+        let th1 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th2 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th3 = network.new_thread(Thread::new(), &proc1).unwrap();
+        assert_eq!(network.scheduler().len(), 3);
+
+        assert_eq!(network.schedule(), Some(th1));
+        assert_eq!(network.schedule(), Some(th2));
+        assert_eq!(network.schedule(), None);
+        assert_eq!(network.active_thread(&th3),
+                Err(Error::NoFreeProcessor(th3)));
+        assert!(*network.thread(&th2).unwrap().state() == ThreadState::Active);
+
+        // Waiting thread frees the processor.
+        let mut ch = Channel::new(th1);
+        ch.add_participant(th2);
+        let ch = network.new_channel(ch).unwrap();
+        network.wait_thread(&th1, &ch, None).unwrap();
+        assert_eq!(network.running_threads().len(), 1);
+        assert_eq!(network.schedule(), Some(th3));
+
+        // Signaled thread goes to the run queue.
+        let woken = network.channel_signal(&th2, &ch, None).unwrap();
+        assert!(woken.contains(&th1));
+        assert!(*network.thread(&th1).unwrap().state() == ThreadState::Sleep);
+        assert_eq!(network.schedule(), Some(th1));
+
+        network.sleep_thread(&th3).unwrap();
+        network.remove_thread(&th3).unwrap();
+        assert!(network.scheduler().is_empty());
+        assert_eq!(network.schedule(), None);
+    }
+
+    #[test]
+    fn network_schedule_priority() {
+        let mut network = Network::new();
+        let proc1 = network.new_process(Process::new(Path::new("a".to_string())))
+                .unwrap();
+
+        let th1 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th2 = network.new_thread(Thread::new(), &proc1).unwrap();
+        network.set_scheduler(Box::new(PriorityScheduler::new()));
+        network.set_priority(&th2, 1).unwrap();
+
+        assert_eq!(network.schedule(), Some(th2));
+        network.sleep_thread(&th2).unwrap();
+        assert_eq!(network.schedule(), Some(th2));
+        network.set_priority(&th2, 0).unwrap();
+        network.sleep_thread(&th2).unwrap();
+        assert_eq!(network.schedule(), Some(th1));
+        assert_eq!(network.set_priority(&(th2 + 1), 1),
+                Err(Error::UnknownThread(th2 + 1)));
+    }
+
+    #[test]
+    fn network_priority_inheritance() {
+        let mut network = Network::new();
+        network.set_scheduler(Box::new(PriorityScheduler::new()));
+        let proc1 = network.new_process(Process::new(Path::new("a".to_string())))
+                .unwrap();
+
+        let mut high = Thread::new();
+        high.set_priority(10);
+        let high = network.new_thread(high, &proc1).unwrap();
+        let low = network.new_thread(Thread::new(), &proc1).unwrap();
+        let medium = network.new_thread(Thread::new(), &proc1).unwrap();
+        let other = network.new_thread(Thread::new(), &proc1).unwrap();
+        network.set_priority(&medium, 5).unwrap();
+
+        let mut ch1 = Channel::new(high);
+        ch1.add_participant(low);
+        let ch1 = network.new_channel(ch1).unwrap();
+        let mut ch2 = Channel::new(low);
+        ch2.add_participant(other);
+        let ch2 = network.new_channel(ch2).unwrap();
+
+        assert_eq!(network.schedule(), Some(high));
+        network.wait_thread(&high, &ch1, None).unwrap();
+        let thread = network.thread(&low).unwrap();
+        assert_eq!(thread.inherited_priority(), Some(10));
+        assert_eq!(thread.effective_priority(), 10);
+
+        // Priority is passed along the chain of waits.
+        network.wait_thread(&low, &ch2, None).unwrap();
+        assert_eq!(network.thread(&other).unwrap().effective_priority(), 10);
+        network.set_priority(&high, 12).unwrap();
+        assert_eq!(network.thread(&other).unwrap().effective_priority(), 12);
+        assert_eq!(network.schedule(), Some(other));
+        network.channel_signal(&other, &ch2, None).unwrap();
+        assert_eq!(network.thread(&other).unwrap().inherited_priority(), None);
+
+        // Low priority thread runs before the medium one.
+        assert_eq!(network.schedule(), Some(low));
+        network.channel_signal(&low, &ch1, None).unwrap();
+        assert_eq!(network.thread(&low).unwrap().effective_priority(), 0);
+        assert_eq!(network.schedule(), Some(high));
+        network.sleep_thread(&high).unwrap();
+        assert_eq!(network.schedule(), Some(high));
+
+        // Timed wait lends no priority, only the signalling thread that
+        // waits without timeout does.
+        network.wait_thread(&high, &ch1, Some(5)).unwrap();
+        assert_eq!(network.thread(&low).unwrap().inherited_priority(), Some(0));
+        assert_eq!(network.schedule(), Some(medium));
+    }
 }
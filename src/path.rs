@@ -1,5 +1,10 @@
-use std::rc::Rc;
-use std::collections::{BTreeMap, LinkedList};
+use std::rc::{Rc, Weak};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use crate::{Access, Error, InterfaceKey, Policy, ProcessKey};
 
 /// Tree that stores all package nodes.
 #[derive(Default)]
@@ -10,8 +15,21 @@ pub struct PackageTree {
 #[derive(Default)]
 struct PackageNode {
     nodes: BTreeMap<String, PackageNode>,
+    entries: Entries,
+    policy: Option<Policy>,
+}
+
+/// Interfaces and processes that are located at the package.
+#[derive(Debug, Default)]
+pub struct Entries {
+    interfaces: BTreeSet<InterfaceKey>,
+    processes: BTreeSet<ProcessKey>,
 }
 
+/// Shared path node. Paths are interned so equal paths always share the
+/// same node and are compared and hashed without walking their nodes.
+/// Ordering uses the key stored in the node, so lookups in ordered maps
+/// such as the one of `InterfaceSet` do not walk the nodes either.
 #[derive(Debug, Clone)]
 pub struct RcPath(Rc<Path>);
 
@@ -20,6 +38,49 @@ pub struct RcPath(Rc<Path>);
 pub struct Path {
     prev_node: Option<RcPath>,
     name: String,
+
+    /// Number of nodes in the path including this one.
+    depth: usize,
+
+    /// Key that orders paths the same way as comparing their nodes one by
+    /// one, see `Path::order_key`. Built once when the node is interned,
+    /// so ordered maps keyed by paths compare keys without walking the
+    /// nodes.
+    order: Box<[u8]>,
+}
+
+/// Path relative to some package. It is written with leading dots: one
+/// dot refers to the package itself and each extra dot goes one level up.
+/// So from the package `a.b` reference `.c` means `a.b.c` and `..c` means
+/// `a.c`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelPath {
+    up: usize,
+    names: Vec<String>,
+}
+
+thread_local! {
+    /// Nodes of all live paths by the address of the parent node (zero
+    /// for top-level nodes) and the name.
+    static INTERNER: RefCell<HashMap<usize, HashMap<String, Weak<Path>>>> =
+            RefCell::new(HashMap::new());
+}
+
+/// Depth-first iterator over all paths stored in the package tree.
+/// Parent goes before it's children, siblings go in alphabetical order.
+pub struct Walk<'a> {
+    stack: Vec<(RcPath, &'a PackageNode)>,
+}
+
+/// Part of the glob pattern.
+enum Glob {
+    Name(String),
+
+    /// Any single node, `*`.
+    One,
+
+    /// Any number of nodes including none, `**`.
+    Many,
 }
 
 /// Iterator over path nodes.
@@ -63,92 +124,557 @@ impl PackageTree {
         }
     }
 
-    /// Remove this path from the tree. Some packages may still remain if
-    /// they store other sub-packages.
-    pub fn remove_path(&mut self, path: &RcPath) {
+    /// Interfaces and processes located at the path. None is returned if
+    /// path is not stored in the tree.
+    pub fn entries(&self, path: &RcPath) -> Option<&Entries> {
+        self.node(path).map(|node| &node.entries)
+    }
+
+    /// Record the interface at it's path. Path is stored if needed.
+    pub(crate) fn add_interface(&mut self, key: &InterfaceKey) {
+        self.node_mut(key.path()).entries.interfaces.insert(key.clone());
+    }
+
+    /// Forget the interface. Package is removed if nothing else is located
+    /// in it. Return true if package was removed.
+    pub(crate) fn remove_interface(&mut self, key: &InterfaceKey) -> bool {
+        if let Some(node) = self.find_node_mut(key.path()) {
+            node.entries.interfaces.remove(key);
+        }
+        self.release(key.path())
+    }
+
+    /// Record the process at given path. Path is stored if needed.
+    pub(crate) fn add_process(&mut self, path: &RcPath, key: ProcessKey) {
+        self.node_mut(path).entries.processes.insert(key);
+    }
+
+    /// Forget the process. Package is removed if nothing else is located
+    /// in it. Return true if package was removed.
+    pub(crate) fn remove_process(&mut self, path: &RcPath, key: &ProcessKey)
+            -> bool {
+        if let Some(node) = self.find_node_mut(path) {
+            node.entries.processes.remove(key);
+        }
+        self.release(path)
+    }
+
+    /// Remove the path if it has no entries and no sub-packages. Return
+    /// true if it was removed.
+    fn release(&mut self, path: &RcPath) -> bool {
+        let unused = match self.node(path) {
+            Some(node) => node.is_unused(),
+            None => false,
+        };
+        if unused {
+            self.remove_path(path);
+        }
+        unused
+    }
+
+    /// Policy that is attached to the path itself.
+    pub fn policy(&self, path: &RcPath) -> Option<&Policy> {
+        self.node(path).and_then(|node| node.policy.as_ref())
+    }
+
+    /// Policy that applies to the path: it's own one or the policy of the
+    /// closest parent that has it.
+    pub fn effective_policy(&self, path: &RcPath) -> Option<&Policy> {
+        let mut cur = &self.root_node;
+        let mut policy = None;
+        for node in PathIter::new(path.clone()) {
+            cur = match cur.nodes.get(node.name()) {
+                Some(next) => next,
+                None => break,
+            };
+            if cur.policy.is_some() {
+                policy = cur.policy.as_ref();
+            }
+        }
+        policy
+    }
+
+    /// Whether process located at given path has the access to the
+    /// interfaces at the target path.
+    pub fn permits(&self, target: &RcPath, access: Access, process: &RcPath)
+            -> bool {
+        match self.effective_policy(target) {
+            Some(policy) => policy.permits(access, process),
+            None => true,
+        }
+    }
+
+    /// Attach the policy to the path. Path is stored if needed.
+    pub(crate) fn set_policy(&mut self, path: &RcPath, policy: Policy) {
+        self.node_mut(path).policy = Some(policy);
+    }
+
+    /// Detach the policy from the path. Package is removed if nothing else
+    /// is located in it.
+    pub(crate) fn remove_policy(&mut self, path: &RcPath) -> Option<Policy> {
+        let policy = self.find_node_mut(path)?.policy.take();
+        self.release(path);
+        policy
+    }
+
+    /// Whether the path is stored in the tree.
+    pub fn exists(&self, path: &RcPath) -> bool {
+        self.node(path).is_some()
+    }
+
+    /// Direct children of the path in alphabetical order. Empty list is
+    /// returned if path is not stored in the tree.
+    pub fn children(&self, path: &RcPath) -> Vec<RcPath> {
+        match self.node(path) {
+            Some(node) => node.nodes.keys()
+                    .map(|name| Path::new_from_parent(path.clone(),
+                            name.clone()))
+                    .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Top-level packages in alphabetical order.
+    pub fn roots(&self) -> Vec<RcPath> {
+        self.root_node.nodes.keys()
+                .map(|name| Path::new(name.clone()))
+                .collect()
+    }
+
+    /// Iterate depth-first over all stored paths.
+    pub fn walk(&self) -> Walk<'_> {
+        let stack = self.root_node.nodes.iter().rev()
+                .map(|(name, node)| (Path::new(name.clone()), node))
+                .collect();
+        Walk {
+            stack,
+        }
+    }
+
+    /// Stored paths that match the glob pattern, in the order of `walk`.
+    /// Pattern consists of names separated by dots where `*` matches any
+    /// single name and `**` matches any number of names including none.
+    /// So `a.*.c` matches `a.b.c` and `a.**` matches `a` and all it's
+    /// sub-packages.
+    ///
+    /// # Errors
+    /// `BadPath` is returned if the pattern is malformed.
+    pub fn glob(&self, pattern: &str) -> Result<Vec<RcPath>, Error> {
+        let mut globs = Vec::new();
+        for name in pattern.split('.') {
+            let glob = match name {
+                "*" => Glob::One,
+                "**" => Glob::Many,
+                _ if name.contains('*') => return Err(Error::BadPath(
+                        pattern.to_string(),
+                        "wildcard must be a whole name".to_string())),
+                _ => {
+                    // Check that the name is valid.
+                    name.parse::<RcPath>().map_err(|e| match e {
+                        Error::BadPath(_, reason) =>
+                            Error::BadPath(pattern.to_string(), reason),
+                        e => e,
+                    })?;
+                    Glob::Name(name.to_string())
+                },
+            };
+            globs.push(glob);
+        }
+
+        Ok(self.walk().filter(|path| {
+            let names: Vec<RcPath> = PathIter::new(path.clone()).collect();
+            let names: Vec<&str> = names.iter().map(|n| n.name()).collect();
+            glob_matches(&globs, &names)
+        }).collect())
+    }
+
+    /// Tree node of the path.
+    fn node(&self, path: &RcPath) -> Option<&PackageNode> {
+        let mut cur = &self.root_node;
+        for node in PathIter::new(path.clone()) {
+            cur = cur.nodes.get(node.name())?;
+        }
+        Some(cur)
+    }
+
+    /// Mutable tree node of the path if it is stored.
+    fn find_node_mut(&mut self, path: &RcPath) -> Option<&mut PackageNode> {
         let mut cur = &mut self.root_node;
-        let mut i = PathIter::new(path.clone());
-        let mut tree_node_path = LinkedList::new();
-        tree_node_path.push_back(cur as *const _);
-        let mut passed = 0; // How many nodes were passed.
+        for node in PathIter::new(path.clone()) {
+            cur = cur.nodes.get_mut(node.name())?;
+        }
+        Some(cur)
+    }
 
-        // Build and save path to last node.
-        loop {
-            // Get next path node.
-            let next = i.next();
-            if next.is_none() {
-                // The last node reached.
-                break;
+    /// Tree node of the path. Path is stored if needed.
+    fn node_mut(&mut self, path: &RcPath) -> &mut PackageNode {
+        self.store_path(path);
+        self.find_node_mut(path).unwrap()
+    }
+
+    /// Paths to all packages that have no sub-packages.
+    pub(crate) fn leaves(&self) -> Vec<RcPath> {
+        let mut leaves = Vec::new();
+        let mut stack: Vec<(Option<RcPath>, &PackageNode)> =
+                vec![(None, &self.root_node)];
+
+        while let Some((path, node)) = stack.pop() {
+            if node.nodes.is_empty() {
+                if let Some(path) = path {
+                    leaves.push(path);
+                }
+                continue;
             }
-            let next = next.unwrap();
 
-            // Find corresponding tree node.
-            let name = &next.name;
-            let corresponding_node = cur.nodes.get_mut(name);
-            if corresponding_node.is_none() {
-                break; // No such node.
+            for (name, sub) in node.nodes.iter().rev() {
+                let sub_path = match path {
+                    Some(ref path) => Path::new_from_parent(path.clone(),
+                            name.clone()),
+                    None => Path::new(name.clone()),
+                };
+                stack.push((Some(sub_path), sub));
             }
-            let corresponding_node = corresponding_node.unwrap();
-            passed += 1;
+        }
+        leaves
+    }
 
-            // Store corresponding node pointer in the list.
-            tree_node_path.push_back(corresponding_node as *const _);
+    /// Remove this path from the tree. Some packages may still remain if
+    /// they store other sub-packages, interfaces, processes or policies.
+    pub fn remove_path(&mut self, path: &RcPath) {
+        let nodes: Vec<RcPath> = PathIter::new(path.clone()).collect();
+        self.root_node.remove_nodes(&nodes);
+    }
+}
 
-            // Move to sub-node.
-            cur = corresponding_node;
+impl PackageNode {
+
+    /// Remove the sub-nodes along given path nodes, starting from the
+    /// deepest one. Nodes that are still in use are kept.
+    fn remove_nodes(&mut self, nodes: &[RcPath]) {
+        let (first, rest) = match nodes.split_first() {
+            Some(split) => split,
+            None => return,
+        };
+
+        let unused = match self.nodes.get_mut(first.name()) {
+            Some(sub) => {
+                sub.remove_nodes(rest);
+                sub.is_unused()
+            },
+            None => return,
+        };
+        if unused {
+            self.nodes.remove(first.name());
         }
+    }
 
-        // Check how many nodes were actually passed and compare to
-        // full path size.
-        let remain = i.len() - passed;
-        if remain != 0 {
-            // Part of path does not exist.
-            // Remove from iterator non-existent nodes.
-            for _ in 0..remain {
-                i.next_back();
+    /// Whether node has nothing in it and may be removed.
+    fn is_unused(&self) -> bool {
+        self.nodes.is_empty() && self.entries.is_empty()
+                && self.policy.is_none()
+    }
+}
+
+impl Entries {
+
+    /// Interfaces located at the package, all versions of them.
+    pub fn interfaces(&self) -> &BTreeSet<InterfaceKey> {
+        &self.interfaces
+    }
+
+    /// Processes located at the package.
+    pub fn processes(&self) -> &BTreeSet<ProcessKey> {
+        &self.processes
+    }
+
+    /// Whether nothing is located at the package.
+    pub fn is_empty(&self) -> bool {
+        self.interfaces.is_empty() && self.processes.is_empty()
+    }
+}
+
+impl RcPath {
+
+    /// Path of the parent package. None for top-level package.
+    pub fn parent(&self) -> Option<RcPath> {
+        self.prev_node.clone()
+    }
+
+    /// Number of nodes in the path. It is 1 for top-level package.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Whether this path is a parent, grand-parent and so on of the other.
+    pub fn is_ancestor_of(&self, other: &RcPath) -> bool {
+        other.depth > self.depth && other.ancestor(self.depth) == self
+    }
+
+    /// Whether this path is equal to the prefix or the prefix is it's
+    /// ancestor.
+    pub fn starts_with(&self, prefix: &RcPath) -> bool {
+        self.depth >= prefix.depth && self.ancestor(prefix.depth) == prefix
+    }
+
+    /// Longest path that both paths start with. None is returned if
+    /// paths have different top-level packages.
+    pub fn common_prefix(&self, other: &RcPath) -> Option<RcPath> {
+        let depth = self.depth.min(other.depth);
+        let mut a = self.ancestor(depth);
+        let mut b = other.ancestor(depth);
+        while a != b {
+            match (&a.prev_node, &b.prev_node) {
+                (Some(i), Some(j)) => {
+                    a = i;
+                    b = j;
+                },
+                _ => return None,
             }
         }
+        Some(a.clone())
+    }
 
-        loop {
-            // Get next node to process.
-            let next = i.next_back();
-            if next.is_none() {
-                // No more nodes. We're done.
-                break;
-            }
-            let next = next.unwrap();
+    /// Path relative to the prefix. None is returned if path does not
+    /// start with the prefix.
+    pub fn strip_prefix(&self, prefix: &RcPath) -> Option<RelPath> {
+        if !self.starts_with(prefix) {
+            return None;
+        }
+        let names = PathIter::new(self.clone()).skip(prefix.depth)
+                .map(|node| node.name.clone())
+                .collect();
+        Some(RelPath {
+            up: 0,
+            names,
+        })
+    }
+
+    /// Path that relative path refers to from this package. None is
+    /// returned if it goes above the top-level packages or refers to none
+    /// of the packages.
+    pub fn join(&self, path: &RelPath) -> Option<RcPath> {
+        let mut base = if path.up < self.depth {
+            Some(self.ancestor(self.depth - path.up).clone())
+        } else if path.up == self.depth {
+            None
+        } else {
+            return None;
+        };
+
+        for name in path.names.iter() {
+            base = Some(match base {
+                Some(parent) => Path::new_from_parent(parent, name.clone()),
+                None => Path::new(name.clone()),
+            });
+        }
+        base
+    }
 
-            let back = tree_node_path.pop_back().unwrap();
-            let back = unsafe { &mut *(back as *mut PackageNode) };
+    /// Resolve the reference from this package. Reference that starts with
+    /// a dot is relative, see `RelPath`. Other references are absolute
+    /// paths.
+    ///
+    /// # Errors
+    /// `BadPath` is returned if reference is malformed or goes above the
+    /// top-level packages.
+    pub fn resolve(&self, reference: &str) -> Result<RcPath, Error> {
+        if !reference.starts_with('.') {
+            return reference.parse();
+        }
+        let path: RelPath = reference.parse()?;
+        match self.join(&path) {
+            Some(path) => Ok(path),
+            None => Err(Error::BadPath(reference.to_string(),
+                    format!("does not refer to a package from '{}'",
+                    Path::to_string(self)))),
+        }
+    }
 
-            if back.nodes.is_empty() {
-                // If this is the last package - remove node completely.
-                let new_back = tree_node_path.back().unwrap();
-                let new_back = unsafe { &mut *(*new_back as *mut PackageNode) };
-                new_back.nodes.remove(&next.name);
+    /// Ancestor with given depth or the path itself. Depth must not exceed
+    /// the depth of the path.
+    fn ancestor(&self, depth: usize) -> &RcPath {
+        let mut node = self;
+        while node.depth > depth {
+            node = node.prev_node.as_ref().unwrap();
+        }
+        node
+    }
+
+    /// Address of the node that identifies the path.
+    fn addr(&self) -> usize {
+        Rc::as_ptr(&self.0) as usize
+    }
+}
+
+impl RelPath {
+
+    /// Number of levels to go up from the package before descending.
+    pub fn up(&self) -> usize {
+        self.up
+    }
+
+    /// Names of the packages to descend into.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+}
+
+impl FromStr for RelPath {
+    type Err = Error;
+
+    /// Parse relative path of the form `..foo.bar`.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let rest = s.trim_start_matches('.');
+        let dots = s.len() - rest.len();
+        if dots == 0 {
+            return Err(Error::BadPath(s.to_string(),
+                    "relative path must start with '.'".to_string()));
+        }
+
+        let names = if rest.is_empty() {
+            Vec::new()
+        } else {
+            let path: RcPath = rest.parse().map_err(|e| match e {
+                Error::BadPath(_, reason) => Error::BadPath(s.to_string(), reason),
+                e => e,
+            })?;
+            PathIter::new(path).map(|node| node.name.clone()).collect()
+        };
+        Ok(RelPath {
+            up: dots - 1,
+            names,
+        })
+    }
+}
+
+impl ::std::fmt::Display for RelPath {
+
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        for _ in 0..=self.up {
+            write!(f, ".")?;
+        }
+        write!(f, "{}", self.names.join("."))
+    }
+}
+
+impl Drop for Path {
+
+    /// Remove the node from the interner.
+    fn drop(&mut self) {
+        let parent_addr = self.prev_node.as_ref().map_or(0, RcPath::addr);
+
+        // Interner may be already destroyed at the thread exit or borrowed
+        // if node is dropped while interning. Dead entry is replaced by
+        // the next interning of the same path then.
+        let _ = INTERNER.try_with(|interner| {
+            if let Ok(mut interner) = interner.try_borrow_mut() {
+                if let Some(nodes) = interner.get_mut(&parent_addr) {
+                    let dead = matches!(nodes.get(&self.name),
+                            Some(node) if node.strong_count() == 0);
+                    if dead {
+                        nodes.remove(&self.name);
+                    }
+                    if nodes.is_empty() {
+                        interner.remove(&parent_addr);
+                    }
+                }
             }
+        });
+    }
+}
+
+impl<'a> Iterator for Walk<'a> {
+
+    type Item = RcPath;
+
+    fn next(&mut self) -> Option<RcPath> {
+        let (path, node) = self.stack.pop()?;
+        for (name, sub) in node.nodes.iter().rev() {
+            self.stack.push((Path::new_from_parent(path.clone(), name.clone()),
+                    sub));
         }
+        Some(path)
+    }
+}
+
+/// Whether names of the path match the glob pattern.
+fn glob_matches(globs: &[Glob], names: &[&str]) -> bool {
+    match globs.split_first() {
+        None => names.is_empty(),
+        Some((Glob::Many, rest)) =>
+            (0..=names.len()).any(|i| glob_matches(rest, &names[i..])),
+        Some((glob, rest)) => match names.split_first() {
+            Some((name, names)) => {
+                let matches = match glob {
+                    Glob::Name(n) => n == name,
+                    _ => true,
+                };
+                matches && glob_matches(rest, names)
+            },
+            None => false,
+        },
     }
 }
 
 impl Path {
 
     /// Create new path without parents.
+    // Paths only exist interned behind RcPath, so that is what is built.
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(name: String) -> RcPath {
-        let rc = Rc::new(Path {
-            prev_node: None,
-            name,
-        });
-        RcPath(rc)
+        Self::intern(None, name)
     }
 
     /// Create new path node with given parent.
     pub fn new_from_parent(parent: RcPath, name: String) -> RcPath {
-        let rc = Rc::new(Path {
-            prev_node: Some(parent),
-            name
-        });
-        RcPath(rc)
+        Self::intern(Some(parent), name)
+    }
+
+    /// Existing node of the path or new one if there is no such path yet.
+    fn intern(parent: Option<RcPath>, name: String) -> RcPath {
+        let parent_addr = parent.as_ref().map_or(0, RcPath::addr);
+        let depth = parent.as_ref().map_or(1, |p| p.depth + 1);
+        let order = Self::order_key(parent.as_ref(), &name);
+
+        INTERNER.with(|interner| {
+            let mut interner = interner.borrow_mut();
+            let nodes = interner.entry(parent_addr).or_default();
+            if let Some(rc) = nodes.get(&name).and_then(Weak::upgrade) {
+                return RcPath(rc);
+            }
+
+            let rc = Rc::new(Path {
+                prev_node: parent,
+                name: name.clone(),
+                depth,
+                order,
+            });
+            nodes.insert(name, Rc::downgrade(&rc));
+            RcPath(rc)
+        })
+    }
+
+    /// Order key of the node with given parent and name.
+    ///
+    /// Paths go in reverse alphabetical order of their names and children
+    /// go before their parent. Each name is written as pairs of the
+    /// inverted byte and zero and is closed by `FF FF`, which reverses the
+    /// order of the names. The whole key is closed by one more `FF FF`,
+    /// which makes the parent greater than any of it's children.
+    fn order_key(parent: Option<&RcPath>, name: &str) -> Box<[u8]> {
+        let mut order = match parent {
+            Some(parent) => {
+                let order = &parent.order;
+                order[..order.len() - 2].to_vec()
+            },
+            None => Vec::with_capacity(name.len() * 2 + 4),
+        };
+        for byte in name.bytes() {
+            order.push(!byte);
+            order.push(0);
+        }
+        order.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF]);
+        order.into_boxed_slice()
     }
 
     /// Convert path to string value.
@@ -184,6 +710,33 @@ impl Path {
     }
 }
 
+impl FromStr for RcPath {
+    type Err = Error;
+
+    /// Parse path of the form `root.foo.bar`. Names of the nodes may
+    /// contain letters, digits, `_` and `-`.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut path: Option<RcPath> = None;
+        for name in s.split('.') {
+            if name.is_empty() {
+                return Err(Error::BadPath(s.to_string(),
+                        "empty name".to_string()));
+            }
+            let valid = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+            if !name.chars().all(valid) {
+                return Err(Error::BadPath(s.to_string(),
+                        format!("bad name '{}'", name)));
+            }
+
+            path = Some(match path {
+                Some(parent) => Path::new_from_parent(parent, name.to_string()),
+                None => Path::new(name.to_string()),
+            });
+        }
+        Ok(path.unwrap())
+    }
+}
+
 impl ::std::borrow::Borrow<Path> for RcPath {
 
     fn borrow(&self) -> &Path {
@@ -206,86 +759,44 @@ impl ::std::ops::Deref for RcPath {
     }
 }
 
-impl Into<Rc<Path>> for RcPath {
+impl From<RcPath> for Rc<Path> {
 
-    fn into(self) -> Rc<Path> {
-        self.0
+    fn from(path: RcPath) -> Rc<Path> {
+        path.0
     }
 }
 
 impl PartialEq for RcPath {
 
+    /// Equal paths share the same interned node.
     fn eq(&self, other: &RcPath) -> bool {
-        let mut a = PathIter::new(self.clone().into());
-        let mut b = PathIter::new(other.clone().into());
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
 
-        loop {
-            let a = a.next();
-            let b = b.next();
+impl Eq for RcPath {}
 
-            if a.is_some() && b.is_some() {
-                let i = a.unwrap();
-                let j = b.unwrap();
+impl Hash for RcPath {
 
-                if i.name != j.name {
-                    return false;
-                }
-            } else {
-                return false;
-            }
-        }
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.addr().hash(state);
     }
 }
 
-impl Eq for RcPath {}
-
 impl PartialOrd for RcPath {
 
     fn partial_cmp(&self, other: &RcPath) -> Option<::std::cmp::Ordering> {
-        use std::cmp::Ordering::*;
-
-        let mut a = PathIter::new(self.clone().into());
-        let mut b = PathIter::new(other.clone().into());
-
-        loop {
-            let i = a.next();
-            let j = b.next();
-
-            if i.is_some() {
-                let i = i.unwrap();
-                if j.is_some() {
-                    let j = j.unwrap();
-
-                    let cmp = i.name.cmp(&j.name);
-                    if cmp == Equal {
-                        continue;
-                    } else {
-                        // Reverse due to string comparison is reverted to
-                        // alphabetical order.
-                        return Some(cmp.reverse());
-                    }
-                } else {
-                    // Children is less than parent.
-                    return Some(Less);
-                }
-            } else {
-                if j.is_some() {
-                    // Root node is greater than children.
-                    return Some(Greater);
-                } else {
-                    // Last nodes are equal as previous. Paths are equal.
-                    return Some(Equal);
-                }
-            }
-        }
-
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for RcPath {
 
     fn cmp(&self, other: &RcPath) -> ::std::cmp::Ordering {
-        self.partial_cmp(other).unwrap()
+        if self == other {
+            return ::std::cmp::Ordering::Equal;
+        }
+        self.order.cmp(&other.order)
     }
 }
 
@@ -293,32 +804,21 @@ impl PathIter {
 
     /// Create path iterator for given path.
     pub fn new(path: RcPath) -> Self {
-        use std::collections::LinkedList;
-
-        let mut list = LinkedList::new();
-
-        // Add current node.
-        list.push_front(path.clone());
-
-        // Add all subnodes.
-        let mut cur = path.prev_node.clone();
-        while cur.is_some() {
-            let unwrap = cur.unwrap();
-            list.push_front(unwrap.clone().into());
-
-            cur = unwrap.prev_node.clone();
-        }
+        let mut nodes = Vec::with_capacity(path.depth);
 
-        // Transform list to array.
-        let mut array = Vec::with_capacity(list.len());
-        for i in list.iter() {
-            array.push(i.clone());
+        // Add current node and all it's parents.
+        let mut cur = Some(path);
+        while let Some(node) = cur {
+            cur = node.prev_node.clone();
+            nodes.push(node);
         }
+        nodes.reverse();
 
+        let len = nodes.len();
         PathIter {
-            nodes: array,
+            nodes,
             cur: 0,
-            curr: list.len(),
+            curr: len,
         }
     }
 }
@@ -373,6 +873,19 @@ mod tests {
         assert_eq!(Path::to_string(&p), "root.foo.bar.baz");
     }
 
+    #[test]
+    fn path_parse() {
+        let p: RcPath = "root.foo.bar".parse().unwrap();
+        assert_eq!(Path::to_string(&p), "root.foo.bar");
+        assert_eq!(PathIter::new(p).len(), 3);
+
+        assert_eq!("root..bar".parse::<RcPath>().unwrap_err(), Error::BadPath(
+                "root..bar".to_string(), "empty name".to_string()));
+        assert!("".parse::<RcPath>().is_err());
+        assert!("root.".parse::<RcPath>().is_err());
+        assert!("root.fo o".parse::<RcPath>().is_err());
+    }
+
     #[test]
     fn path_iter() {
         let p = Path::new("root".to_string());
@@ -423,6 +936,39 @@ mod tests {
         assert!(p0 < p1);
     }
 
+    #[test]
+    fn path_cmp_order_key() {
+        // Same order as comparing names one by one: names in reverse
+        // alphabetical order and children before their parent.
+        let by_names = |a: &RcPath, b: &RcPath| {
+            let a: Vec<_> = PathIter::new(a.clone()).collect();
+            let b: Vec<_> = PathIter::new(b.clone()).collect();
+            for (a, b) in a.iter().zip(b.iter()) {
+                if a.name() != b.name() {
+                    return a.name().cmp(b.name()).reverse();
+                }
+            }
+            b.len().cmp(&a.len())
+        };
+
+        let paths: Vec<RcPath> = ["a", "a.b", "a.bc", "a.b.c", "b", "ab",
+                "a.c", "b.a.z", "a-b", "a.b\0"].iter()
+                .map(|s| {
+                    let mut names = s.split('.');
+                    let first = Path::new(names.next().unwrap().to_string());
+                    names.fold(first, |p, name| {
+                        Path::new_from_parent(p, name.to_string())
+                    })
+                })
+                .collect();
+        for a in paths.iter() {
+            for b in paths.iter() {
+                assert_eq!(a.cmp(b), by_names(a, b), "{:?} {:?}",
+                        Path::to_string(a), Path::to_string(b));
+            }
+        }
+    }
+
     #[test]
     fn package_tree_adding_first() {
         let mut pt = PackageTree::new();
@@ -435,10 +981,10 @@ mod tests {
         pt.store_path(&p0);
 
         let root = &pt.root_node.nodes;
-        let a = root.get(&"a".to_string()).unwrap();
-        let b = a.nodes.get(&"b".to_string()).unwrap();
-        let c = b.nodes.get(&"c".to_string()).unwrap();
-        let d = c.nodes.get(&"d".to_string()).unwrap();
+        let a = root.get("a").unwrap();
+        let b = a.nodes.get("b").unwrap();
+        let c = b.nodes.get("c").unwrap();
+        assert!(c.nodes.contains_key("d"));
     }
 
     #[test]
@@ -454,10 +1000,10 @@ mod tests {
         pt.remove_path(&p0);
 
         let root = &pt.root_node.nodes;
-        let a = root.get(&"a".to_string()).unwrap();
-        let b = a.nodes.get(&"b".to_string()).unwrap();
-        let c = b.nodes.get(&"c".to_string()).unwrap();
-        assert!(c.nodes.get(&"d".to_string()).is_some());
+        let a = root.get("a").unwrap();
+        let b = a.nodes.get("b").unwrap();
+        let c = b.nodes.get("c").unwrap();
+        assert!(c.nodes.contains_key("d"));
     }
 
 
@@ -474,6 +1020,177 @@ mod tests {
         pt.remove_path(&p1);
 
         let root = &pt.root_node.nodes;
-        assert!(root.get(&"a".to_string()).is_none());
+        assert!(root.get("a").is_none());
+    }
+
+    #[test]
+    fn package_tree_queries() {
+        let mut pt = PackageTree::new();
+        for path in &["a.b.c", "a.d.c", "a.d.e", "b"] {
+            pt.store_path(&path.parse().unwrap());
+        }
+        let strings = |paths: Vec<RcPath>| -> Vec<String> {
+            paths.iter().map(Path::to_string).collect()
+        };
+        let path = |s: &str| -> RcPath { s.parse().unwrap() };
+
+        assert!(pt.exists(&path("a.d")));
+        assert!(!pt.exists(&path("a.c")));
+        assert_eq!(strings(pt.roots()), vec!["a", "b"]);
+        assert_eq!(strings(pt.children(&path("a.d"))), vec!["a.d.c", "a.d.e"]);
+        assert!(pt.children(&path("x")).is_empty());
+
+        assert_eq!(strings(pt.walk().collect()),
+                vec!["a", "a.b", "a.b.c", "a.d", "a.d.c", "a.d.e", "b"]);
+
+        assert_eq!(strings(pt.glob("a.*.c").unwrap()), vec!["a.b.c", "a.d.c"]);
+        assert_eq!(strings(pt.glob("a.d.**").unwrap()),
+                vec!["a.d", "a.d.c", "a.d.e"]);
+        assert_eq!(strings(pt.glob("**.c").unwrap()), vec!["a.b.c", "a.d.c"]);
+        assert_eq!(strings(pt.glob("*").unwrap()), vec!["a", "b"]);
+        assert!(pt.glob("x.**").unwrap().is_empty());
+
+        assert_eq!(pt.glob("a.b*").unwrap_err(), Error::BadPath(
+                "a.b*".to_string(), "wildcard must be a whole name".to_string()));
+        assert!(pt.glob("a..b").is_err());
+    }
+
+    #[test]
+    fn package_tree_entries() {
+        let mut pt = PackageTree::new();
+        let path: RcPath = "a.b".parse().unwrap();
+        let v1: InterfaceKey = "a.b@1.0.0".parse().unwrap();
+        let v2: InterfaceKey = "a.b@2.0.0".parse().unwrap();
+
+        pt.add_interface(&v1);
+        pt.add_interface(&v2);
+        pt.add_process(&path, 3);
+        let entries = pt.entries(&path).unwrap();
+        assert_eq!(entries.interfaces().len(), 2);
+        assert!(entries.processes().contains(&3));
+        assert!(pt.entries(&"a".parse().unwrap()).unwrap().is_empty());
+
+        assert!(!pt.remove_interface(&v1));
+        assert!(!pt.remove_process(&path, &3));
+        // Path with entries is not removed.
+        pt.remove_path(&path);
+        assert!(pt.exists(&path));
+
+        assert!(pt.remove_interface(&v2));
+        assert!(!pt.exists(&"a".parse().unwrap()));
+        assert!(pt.entries(&path).is_none());
+    }
+
+    #[test]
+    fn path_intern() {
+        use std::collections::HashSet;
+
+        let p0: RcPath = "intern.a.b".parse().unwrap();
+        let p1 = Path::new_from_parent("intern.a".parse().unwrap(),
+                "b".to_string());
+        assert!(Rc::ptr_eq(&p0, &p1));
+
+        let mut set = HashSet::new();
+        set.insert(p0.clone());
+        assert!(!set.insert(p1.clone()));
+
+        // Dropped paths leave the interner.
+        drop((p0, p1, set));
+        let interned = |name: &str| INTERNER.with(|i| {
+            i.borrow().values().any(|nodes| nodes.contains_key(name))
+        });
+        assert!(!interned("intern"));
+        let p2: RcPath = "intern.a.b".parse().unwrap();
+        assert!(interned("intern"));
+        assert_eq!(Path::to_string(&p2), "intern.a.b");
+    }
+
+    #[test]
+    fn path_algebra() {
+        let path = |s: &str| -> RcPath { s.parse().unwrap() };
+        let p = path("a.b.c");
+
+        assert_eq!(p.parent(), Some(path("a.b")));
+        assert_eq!(path("a").parent(), None);
+        assert_eq!(p.depth(), 3);
+        assert!(path("a").is_ancestor_of(&p));
+        assert!(!p.is_ancestor_of(&p));
+        assert!(!path("a.c").is_ancestor_of(&p));
+        assert!(p.starts_with(&p));
+        assert!(p.starts_with(&path("a.b")));
+        assert!(!path("a").starts_with(&p));
+
+        assert_eq!(p.common_prefix(&path("a.b.d.e")), Some(path("a.b")));
+        assert_eq!(p.common_prefix(&path("a")), Some(path("a")));
+        assert_eq!(p.common_prefix(&path("b.b.c")), None);
+
+        let rel = p.strip_prefix(&path("a")).unwrap();
+        assert_eq!(rel.to_string(), ".b.c");
+        assert_eq!(path("a").join(&rel), Some(p.clone()));
+        assert_eq!(p.strip_prefix(&p).unwrap().to_string(), ".");
+        assert_eq!(p.strip_prefix(&path("b")), None);
+
+        assert_eq!(p.resolve(".d"), Ok(path("a.b.c.d")));
+        assert_eq!(p.resolve("..d"), Ok(path("a.b.d")));
+        assert_eq!(p.resolve("..."), Ok(path("a")));
+        assert_eq!(p.resolve("....x"), Ok(path("x")));
+        assert_eq!(p.resolve("x.y"), Ok(path("x.y")));
+        assert!(p.resolve("....").is_err());
+        assert!(p.resolve(".....x").is_err());
+        assert!(p.resolve("..d.").is_err());
+    }
+
+    #[test]
+    fn rel_path_parse() {
+        let rel: RelPath = "..a.b".parse().unwrap();
+        assert_eq!(rel.up(), 1);
+        assert_eq!(rel.names(), ["a".to_string(), "b".to_string()]);
+        assert_eq!(rel.to_string(), "..a.b");
+
+        assert_eq!("a.b".parse::<RelPath>().unwrap_err(), Error::BadPath(
+                "a.b".to_string(),
+                "relative path must start with '.'".to_string()));
+        assert_eq!("..a..b".parse::<RelPath>().unwrap_err(), Error::BadPath(
+                "..a..b".to_string(), "empty name".to_string()));
+    }
+
+    #[test]
+    fn package_tree_policy() {
+        let mut pt = PackageTree::new();
+        let path = |s: &str| -> RcPath { s.parse().unwrap() };
+
+        let mut policy = Policy::new();
+        policy.allow(Access::Implement, path("sys"));
+        pt.set_policy(&path("a"), policy);
+        pt.set_policy(&path("a.b.open"), Policy::new());
+
+        assert!(pt.policy(&path("a.b")).is_none());
+        assert!(pt.effective_policy(&path("a.b")).is_some());
+        assert!(pt.effective_policy(&path("x")).is_none());
+        assert!(!pt.permits(&path("a.b.c"), Access::Implement, &path("usr")));
+        assert!(pt.permits(&path("a.b.c"), Access::Implement, &path("sys.x")));
+        assert!(pt.permits(&path("a.b.open.c"), Access::Implement,
+                &path("usr")));
+
+        // Path with the policy is kept.
+        pt.remove_path(&path("a.b.open"));
+        assert!(pt.exists(&path("a.b.open")));
+        assert!(pt.remove_policy(&path("a.b.open")).is_some());
+        assert!(!pt.exists(&path("a.b")));
+    }
+
+    #[test]
+    fn path_eq() {
+        let p0 = Path::new("a".to_string());
+        let p0 = Path::new_from_parent(p0, "b".to_string());
+
+        let p1 = Path::new("a".to_string());
+        let p1 = Path::new_from_parent(p1, "b".to_string());
+
+        let p2 = Path::new_from_parent(p1.clone(), "c".to_string());
+
+        assert!(p0 == p1);
+        assert!(p1 != p2);
+        assert!(p2 != p1);
     }
 }
@@ -0,0 +1,104 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::RcPath;
+
+/// Kind of access to the interfaces of the package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Access {
+
+    /// Implementing interfaces of the package.
+    Implement,
+
+    /// Opening channels to implementers of interfaces of the package.
+    Connect,
+}
+
+/// Access control policy of the package. Policy applies to the package
+/// and all it's sub-packages unless they have their own policy.
+///
+/// Each kind of access is either allowed to any process or only to
+/// processes which paths start with one of the allowed prefixes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Policy {
+    implementers: Option<BTreeSet<RcPath>>,
+    clients: Option<BTreeSet<RcPath>>,
+}
+
+impl Policy {
+
+    /// Create new policy that allows any access.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Allow the access to processes located at given prefix. Access is
+    /// denied to all other processes that were not allowed explicitly.
+    pub fn allow(&mut self, access: Access, prefix: RcPath) {
+        self.restrict(access);
+        self.prefixes_mut(access).as_mut().unwrap().insert(prefix);
+    }
+
+    /// Deny the access to all processes that were not allowed explicitly.
+    pub fn restrict(&mut self, access: Access) {
+        self.prefixes_mut(access).get_or_insert_with(Default::default);
+    }
+
+    /// Prefixes of the processes which are allowed the access. None if
+    /// access is not restricted.
+    pub fn allowed(&self, access: Access) -> Option<&BTreeSet<RcPath>> {
+        match access {
+            Access::Implement => self.implementers.as_ref(),
+            Access::Connect => self.clients.as_ref(),
+        }
+    }
+
+    /// Whether process located at given path has the access.
+    pub fn permits(&self, access: Access, process: &RcPath) -> bool {
+        match self.allowed(access) {
+            Some(prefixes) => prefixes.iter()
+                    .any(|prefix| process.starts_with(prefix)),
+            None => true,
+        }
+    }
+
+    fn prefixes_mut(&mut self, access: Access)
+            -> &mut Option<BTreeSet<RcPath>> {
+        match access {
+            Access::Implement => &mut self.implementers,
+            Access::Connect => &mut self.clients,
+        }
+    }
+}
+
+impl fmt::Display for Access {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Access::Implement => write!(f, "implement"),
+            Access::Connect => write!(f, "connect"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policy_permits() {
+        let path = |s: &str| -> RcPath { s.parse().unwrap() };
+        let mut policy = Policy::new();
+        assert!(policy.permits(Access::Implement, &path("a")));
+
+        policy.allow(Access::Implement, path("sys.fs"));
+        assert!(policy.permits(Access::Implement, &path("sys.fs")));
+        assert!(policy.permits(Access::Implement, &path("sys.fs.local")));
+        assert!(!policy.permits(Access::Implement, &path("sys")));
+        assert!(policy.permits(Access::Connect, &path("user")));
+
+        policy.restrict(Access::Connect);
+        assert!(!policy.permits(Access::Connect, &path("user")));
+        assert!(policy.allowed(Access::Connect).unwrap().is_empty());
+    }
+}
@@ -1,14 +1,17 @@
 use crate::{
+    Error,
     RcPath,
     ThreadKey,
     InterfaceKey,
     InterfaceSet,
+    InterfaceFunc,
 };
 
 use std::collections::{
     BTreeSet,
     BTreeMap,
 };
+use std::collections::btree_map::IterMut;
 
 /// Key value to identify unique processes.
 pub type Key = u32;
@@ -19,6 +22,9 @@ pub struct Process {
     path: RcPath,
     threads: BTreeSet<ThreadKey>,
     implements: BTreeSet<InterfaceKey>,
+
+    /// Functions that are provided for each implemented interface.
+    fns: BTreeMap<InterfaceKey, BTreeSet<InterfaceFunc>>,
 }
 
 /// Set that contains processes.
@@ -27,9 +33,27 @@ pub struct Set {
     procs: BTreeMap<Key, Process>,
 }
 
+/// Summary of the process removal from the network.
+pub struct Removal {
+    process: Process,
+    threads: BTreeSet<ThreadKey>,
+    blocked: BTreeSet<ThreadKey>,
+    package_removed: bool,
+}
+
 /// Conflicts that were found in interface implementer.
 pub struct ImplementationConflicts {
     missing: BTreeSet<InterfaceKey>,
+    unknown: BTreeSet<InterfaceKey>,
+    fns: BTreeMap<InterfaceKey, FuncConflicts>,
+}
+
+/// Conflicts between functions of the interface and the functions that
+/// are provided by the implementer.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FuncConflicts {
+    missing: BTreeSet<InterfaceFunc>,
+    mismatched: BTreeSet<(InterfaceFunc, InterfaceFunc)>,
 }
 
 impl Process {
@@ -40,6 +64,7 @@ impl Process {
             path,
             threads: Default::default(),
             implements: Default::default(),
+            fns: Default::default(),
         }
     }
 
@@ -59,28 +84,50 @@ impl Process {
         self.threads.insert(key)
     }
 
+    /// Detach given thread from this process. Return true if it was
+    /// attached and false otherwise.
+    pub fn detach_thread(&mut self, key: &ThreadKey) -> bool {
+        self.threads.remove(key)
+    }
+
     /// Add new interface that is implemented by this process.
     /// Return true if it is already attached and false otherwise.
     pub fn add_implementation(&mut self, key: InterfaceKey) -> bool {
         self.implements.insert(key)
     }
 
+    /// Remove interface with all it's provided functions from the
+    /// implemented ones. Return true if it was implemented and false
+    /// otherwise.
+    pub fn remove_implementation(&mut self, key: &InterfaceKey) -> bool {
+        self.fns.remove(key);
+        self.implements.remove(key)
+    }
+
+    /// Functions that are provided by the process for given interface.
+    pub fn fn_implementations(&self, interface: &InterfaceKey)
+            -> Option<&BTreeSet<InterfaceFunc>> {
+        self.fns.get(interface)
+    }
+
+    /// Add function that is provided by this process for given interface.
+    /// Interface is added to implemented ones if it is not there yet.
+    /// Return true if function was not provided before and false otherwise.
+    pub fn add_fn_implementation(&mut self, interface: InterfaceKey,
+            func: InterfaceFunc) -> bool {
+        self.implements.insert(interface.clone());
+        self.fns.entry(interface).or_default().insert(func)
+    }
+
     /// Check whether there are confliting requirements for interface
-    /// implementer.
+    /// implementer. Prerequisites are checked transitively.
     ///
     /// Interface set is used to retrieve information about interfaces.
-    ///
-    /// # Panics
-    /// Panic occurs when specified interface key is not found in the set.
     pub fn verify_implementations(&self, interface_set: &InterfaceSet)
             -> Result<(), ImplementationConflicts> {
-        let mut prerequisites = BTreeSet::new();
-
         // Collect list of all prerequisites.
-        for interface in &self.implements {
-            let interface = interface_set.interface(&interface).unwrap();
-            prerequisites.append(&mut interface.prerequisites().clone());
-        }
+        let (prerequisites, unknown) =
+                interface_set.walk_prerequisites(&self.implements);
 
         // Check whether all prerequisites are implemented.
         let mut missing = BTreeSet::new();
@@ -90,9 +137,48 @@ impl Process {
             }
         }
 
-        if !missing.is_empty() {
+        // Check whether all functions of implemented interfaces are
+        // provided.
+        let mut fns = BTreeMap::new();
+        let no_fns = BTreeSet::new();
+        for key in &self.implements {
+            let interface = match interface_set.interface(key) {
+                Some(interface) => interface,
+                None => continue,
+            };
+            let provided = self.fns.get(key).unwrap_or(&no_fns);
+
+            let mut conflicts = FuncConflicts::default();
+            for func in interface.fns() {
+                // Exact match by name, version and signature.
+                if provided.iter().any(|f| f == func) {
+                    continue;
+                }
+
+                // Mismatch is reported against the function of the same
+                // version if there is one, or else of the same name.
+                let same_name = || provided.iter()
+                        .filter(|f| f.name() == func.name());
+                let other = same_name()
+                        .find(|f| f.version() == func.version())
+                        .or_else(|| same_name().next());
+                match other {
+                    Some(other) => conflicts.mismatched.insert(
+                            (func.clone(), other.clone())),
+                    None => conflicts.missing.insert(func.clone()),
+                };
+            }
+
+            if !conflicts.is_empty() {
+                fns.insert(key.clone(), conflicts);
+            }
+        }
+
+        if !missing.is_empty() || !unknown.is_empty() || !fns.is_empty() {
             Err(ImplementationConflicts {
-                missing
+                missing,
+                unknown,
+                fns,
             })
         } else {
             Ok(())
@@ -127,22 +213,67 @@ impl Set {
         &self.procs
     }
 
-    pub fn insert(&mut self, key: Key, process: Process) -> bool {
+    /// Iterator over processes that allows to modify them.
+    pub fn iter_mut(&mut self) -> IterMut<'_, Key, Process> {
+        self.procs.iter_mut()
+    }
+
+    /// Add new process to the set. If there is present process with
+    /// same key, new process will be discarded and `ProcessExists` error
+    /// returned.
+    pub fn insert(&mut self, key: Key, process: Process) -> Result<(), Error> {
         if self.procs.contains_key(&key) {
-            return true;
+            return Err(Error::ProcessExists(key));
         }
         self.procs.insert(key, process);
-        false
+        Ok(())
     }
 
-    pub fn remove(&mut self, key: &Key) -> bool {
-        match self.procs.remove(&key) {
-            Some(_) => true,
-            None    => false,
+    /// Remove process from the set. Returns removed process on success and
+    /// `UnknownProcess` error if process was not found.
+    pub fn remove(&mut self, key: &Key) -> Result<Process, Error> {
+        match self.procs.remove(key) {
+            Some(process) => Ok(process),
+            None => Err(Error::UnknownProcess(*key)),
         }
     }
 }
 
+impl Removal {
+
+    pub(crate) fn new(process: Process, threads: BTreeSet<ThreadKey>,
+            blocked: BTreeSet<ThreadKey>, package_removed: bool) -> Self {
+        Removal {
+            process,
+            threads,
+            blocked,
+            package_removed,
+        }
+    }
+
+    /// The process that was removed.
+    pub fn process(&self) -> &Process {
+        &self.process
+    }
+
+    /// Threads of the process that were removed with it.
+    pub fn threads(&self) -> &BTreeSet<ThreadKey> {
+        &self.threads
+    }
+
+    /// Threads of other processes that were waiting for a signal on
+    /// channels shared with removed threads.
+    pub fn blocked(&self) -> &BTreeSet<ThreadKey> {
+        &self.blocked
+    }
+
+    /// Whether the package of the process was removed from the package tree
+    /// because nothing else used it.
+    pub fn package_removed(&self) -> bool {
+        self.package_removed
+    }
+}
+
 impl ImplementationConflicts {
 
     /// Interfaces that are required but are not implemented.
@@ -155,6 +286,38 @@ impl ImplementationConflicts {
     pub fn missing(&self) -> &BTreeSet<InterfaceKey> {
         &self.missing
     }
+
+    /// Interfaces that are implemented or required but are not found in
+    /// the interface set.
+    pub fn unknown(&self) -> &BTreeSet<InterfaceKey> {
+        &self.unknown
+    }
+
+    /// Conflicts of the functions for each implemented interface that
+    /// has them.
+    pub fn fns(&self) -> &BTreeMap<InterfaceKey, FuncConflicts> {
+        &self.fns
+    }
+}
+
+impl FuncConflicts {
+
+    /// Functions of the interface that are not provided.
+    pub fn missing(&self) -> &BTreeSet<InterfaceFunc> {
+        &self.missing
+    }
+
+    /// Functions that are provided with the version or the signature which
+    /// differs from the one in the interface. Each pair contains the
+    /// function of the interface and the provided one.
+    pub fn mismatched(&self) -> &BTreeSet<(InterfaceFunc, InterfaceFunc)> {
+        &self.mismatched
+    }
+
+    /// Whether there are no conflicts.
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.mismatched.is_empty()
+    }
 }
 
 #[cfg(test)]
@@ -180,9 +343,9 @@ mod tests {
         i.add_prerequisite(ik2.clone());
 
         let mut is = InterfaceSet::new();
-        is.add_interface(ik0.clone(), i.clone());
-        is.add_interface(ik1.clone(), i.clone());
-        is.add_interface(ik2.clone(), i.clone());
+        is.add_interface(ik0.clone(), i.clone()).unwrap();
+        is.add_interface(ik1.clone(), Interface::new()).unwrap();
+        is.add_interface(ik2.clone(), Interface::new()).unwrap();
 
         let mut process = Process::new(p0);
 
@@ -192,4 +355,76 @@ mod tests {
         let result = process.verify_implementations(&is);
         assert!(result.unwrap_err().missing.contains(&ik2));
     }
+
+    #[test]
+    fn implementation_verification_transitive() {
+        let k = |s: &str| -> InterfaceKey { s.parse().unwrap() };
+
+        let mut i = Interface::new();
+        i.add_prerequisite(k("a.c@1.0.0"));
+        let mut is = InterfaceSet::new();
+        is.add_interface(k("a.b@1.0.0"), i).unwrap();
+        let mut i = Interface::new();
+        i.add_prerequisite(k("a.d@1.0.0"));
+        is.add_interface(k("a.c@1.0.0"), i).unwrap();
+
+        let mut process = Process::new("a".parse().unwrap());
+        process.add_implementation(k("a.b@1.0.0"));
+        process.add_implementation(k("a.c@1.0.0"));
+        process.add_implementation(k("a.e@1.0.0"));
+
+        let conflicts = process.verify_implementations(&is).unwrap_err();
+        assert!(conflicts.missing().is_empty());
+        let unknown: Vec<_> = conflicts.unknown().iter().cloned().collect();
+        assert_eq!(unknown, vec![k("a.e@1.0.0"), k("a.d@1.0.0")]);
+
+        is.add_interface(k("a.d@1.0.0"), Interface::new()).unwrap();
+        is.add_interface(k("a.e@1.0.0"), Interface::new()).unwrap();
+        let conflicts = process.verify_implementations(&is).unwrap_err();
+        assert!(conflicts.missing().contains(&k("a.d@1.0.0")));
+        assert!(conflicts.unknown().is_empty());
+
+        process.add_implementation(k("a.d@1.0.0"));
+        assert!(process.verify_implementations(&is).is_ok());
+    }
+
+    #[test]
+    fn implementation_verification_fns() {
+        let k: InterfaceKey = "a.b@1.0.0".parse().unwrap();
+        let f = |name: &str, major| {
+            InterfaceFunc::new(name.to_string(), Version::new(major, 0, 0))
+        };
+
+        let mut i = Interface::new();
+        i.add_fn(f("open", 1));
+        i.add_fn(f("read", 2));
+        i.add_fn(f("close", 1));
+        let mut is = InterfaceSet::new();
+        is.add_interface(k.clone(), i).unwrap();
+
+        let mut process = Process::new("a".parse().unwrap());
+        assert!(process.add_fn_implementation(k.clone(), f("open", 1)));
+        assert!(!process.add_fn_implementation(k.clone(), f("open", 1)));
+        process.add_fn_implementation(k.clone(), f("read", 1));
+        assert!(process.implementations().contains(&k));
+
+        let conflicts = process.verify_implementations(&is).unwrap_err();
+        let fns = &conflicts.fns()[&k];
+        assert_eq!(fns.missing().iter().collect::<Vec<_>>(), vec![&f("close", 1)]);
+        assert!(fns.mismatched().contains(&(f("read", 2), f("read", 1))));
+
+        // Other signature of the same version is paired with the required
+        // function rather than the older version.
+        let read = InterfaceFunc::with_signature("read".to_string(),
+                Version::new(2, 0, 0), "(n: u32)".parse().unwrap());
+        process.add_fn_implementation(k.clone(), read.clone());
+        let conflicts = process.verify_implementations(&is).unwrap_err();
+        let fns = &conflicts.fns()[&k];
+        assert_eq!(fns.mismatched().iter().collect::<Vec<_>>(),
+                vec![&(f("read", 2), read)]);
+
+        process.add_fn_implementation(k.clone(), f("read", 2));
+        process.add_fn_implementation(k.clone(), f("close", 1));
+        assert!(process.verify_implementations(&is).is_ok());
+    }
 }
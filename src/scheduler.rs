@@ -0,0 +1,232 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::ThreadKey;
+
+/// Priority of the thread. Threads with greater priority run first.
+pub type Priority = u32;
+
+/// Scheduler decides which of the threads that wait for processor time
+/// runs next. It holds the run queue of such threads.
+pub trait Scheduler {
+
+    /// Add thread that is ready to run to the run queue. Thread that is
+    /// already in the queue keeps it's place.
+    fn push(&mut self, thread: ThreadKey);
+
+    /// Remove thread from the run queue. Returns true if thread was in
+    /// the queue and false otherwise.
+    fn remove(&mut self, thread: &ThreadKey) -> bool;
+
+    /// Forget the thread that was removed from the network. It is removed
+    /// from the run queue along with anything else the scheduler keeps
+    /// about it.
+    fn forget(&mut self, thread: &ThreadKey) {
+        self.remove(thread);
+    }
+
+    /// Take the thread that runs next from the run queue.
+    fn pop(&mut self) -> Option<ThreadKey>;
+
+    /// Threads in the run queue in the order they would run.
+    fn queue(&self) -> Vec<ThreadKey>;
+
+    /// Number of threads in the run queue.
+    fn len(&self) -> usize;
+
+    /// Whether the run queue is empty.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Change priority of the thread. Schedulers that do not use
+    /// priorities ignore it.
+    fn set_priority(&mut self, _thread: &ThreadKey, _priority: Priority) {}
+
+    /// Name of the scheduler in network snapshots. Snapshot loader only
+    /// knows the schedulers of this crate, others have no name and are
+    /// loaded as round-robin scheduler.
+    fn name(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// Scheduler that runs threads in the order they became ready.
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    queue: VecDeque<ThreadKey>,
+}
+
+/// Scheduler that runs threads with greatest priority first. Threads with
+/// equal priority run in the order they became ready.
+#[derive(Debug, Default)]
+pub struct PriorityScheduler {
+
+    /// Priorities of the threads. Threads that are not in the map have
+    /// zero priority.
+    priorities: BTreeMap<ThreadKey, Priority>,
+
+    /// Run queue for each priority.
+    queues: BTreeMap<Priority, VecDeque<ThreadKey>>,
+}
+
+impl RoundRobin {
+
+    /// Create new scheduler with empty run queue.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Scheduler for RoundRobin {
+
+    fn push(&mut self, thread: ThreadKey) {
+        if !self.queue.contains(&thread) {
+            self.queue.push_back(thread);
+        }
+    }
+
+    fn remove(&mut self, thread: &ThreadKey) -> bool {
+        let len = self.queue.len();
+        self.queue.retain(|t| t != thread);
+        len != self.queue.len()
+    }
+
+    fn pop(&mut self) -> Option<ThreadKey> {
+        self.queue.pop_front()
+    }
+
+    fn queue(&self) -> Vec<ThreadKey> {
+        self.queue.iter().cloned().collect()
+    }
+
+    fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        Some("round-robin")
+    }
+}
+
+impl PriorityScheduler {
+
+    /// Create new scheduler with empty run queue.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Priority of the thread.
+    pub fn priority(&self, thread: &ThreadKey) -> Priority {
+        match self.priorities.get(thread) {
+            Some(priority) => *priority,
+            None => 0,
+        }
+    }
+}
+
+impl Scheduler for PriorityScheduler {
+
+    fn push(&mut self, thread: ThreadKey) {
+        let priority = self.priority(&thread);
+        let queue = self.queues.entry(priority).or_default();
+        if !queue.contains(&thread) {
+            queue.push_back(thread);
+        }
+    }
+
+    fn remove(&mut self, thread: &ThreadKey) -> bool {
+        let priority = self.priority(thread);
+        let queue = match self.queues.get_mut(&priority) {
+            Some(queue) => queue,
+            None => return false,
+        };
+
+        let len = queue.len();
+        queue.retain(|t| t != thread);
+        let removed = len != queue.len();
+        if queue.is_empty() {
+            self.queues.remove(&priority);
+        }
+        removed
+    }
+
+    fn forget(&mut self, thread: &ThreadKey) {
+        self.remove(thread);
+        self.priorities.remove(thread);
+    }
+
+    fn pop(&mut self) -> Option<ThreadKey> {
+        let priority = *self.queues.keys().next_back()?;
+        let queue = self.queues.get_mut(&priority).unwrap();
+        let thread = queue.pop_front();
+        if queue.is_empty() {
+            self.queues.remove(&priority);
+        }
+        thread
+    }
+
+    fn queue(&self) -> Vec<ThreadKey> {
+        self.queues.values().rev().flatten().cloned().collect()
+    }
+
+    fn len(&self) -> usize {
+        self.queues.values().map(|queue| queue.len()).sum()
+    }
+
+    fn set_priority(&mut self, thread: &ThreadKey, priority: Priority) {
+        let queued = self.remove(thread);
+        self.priorities.insert(*thread, priority);
+        if queued {
+            self.push(*thread);
+        }
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        Some("priority")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_robin_order() {
+        let mut s = RoundRobin::new();
+        s.push(1);
+        s.push(2);
+        s.push(3);
+        s.push(1);
+        assert_eq!(s.len(), 3);
+        assert_eq!(s.queue(), vec![1, 2, 3]);
+
+        assert!(s.remove(&2));
+        assert!(!s.remove(&2));
+
+        assert_eq!(s.pop(), Some(1));
+        assert_eq!(s.pop(), Some(3));
+        assert_eq!(s.pop(), None);
+    }
+
+    #[test]
+    fn priority_order() {
+        let mut s = PriorityScheduler::new();
+        s.set_priority(&3, 5);
+        s.push(1);
+        s.push(2);
+        s.push(3);
+        s.set_priority(&2, 7);
+        assert_eq!(s.len(), 3);
+        assert_eq!(s.queue(), vec![2, 3, 1]);
+
+        assert_eq!(s.pop(), Some(2));
+        assert_eq!(s.pop(), Some(3));
+        assert_eq!(s.pop(), Some(1));
+        assert!(s.is_empty());
+
+        s.push(3);
+        s.forget(&3);
+        assert!(s.is_empty());
+        assert_eq!(s.priority(&3), 0);
+    }
+}
@@ -0,0 +1,1135 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::{
+    Access,
+    Channel,
+    ChannelKey,
+    Error,
+    Interface,
+    InterfaceFunc,
+    InterfaceKey,
+    Network,
+    Path,
+    PathIter,
+    Policy,
+    PriorityScheduler,
+    Process,
+    ProcessKey,
+    RcPath,
+    RoundRobin,
+    Signature,
+    Thread,
+    ThreadKey,
+    ThreadState,
+    Version,
+    WakeReason,
+};
+
+/// Version of the snapshot format that is written by `Network::save`.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// First word of each snapshot.
+const MAGIC: &str = "ccs-snapshot";
+
+/// Payload of the messages that can be saved to the snapshot.
+pub trait Payload: Sized {
+
+    /// Text representation of the payload.
+    fn encode(&self) -> String;
+
+    /// Payload from it's text representation. None is returned if text
+    /// is malformed.
+    fn decode(text: &str) -> Option<Self>;
+}
+
+impl Payload for () {
+
+    fn encode(&self) -> String {
+        String::new()
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        if text.is_empty() {
+            Some(())
+        } else {
+            None
+        }
+    }
+}
+
+impl Payload for String {
+
+    fn encode(&self) -> String {
+        self.clone()
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        Some(text.to_string())
+    }
+}
+
+macro_rules! impl_payload {
+    ($($t:ty),*) => {$(
+        impl Payload for $t {
+
+            fn encode(&self) -> String {
+                self.to_string()
+            }
+
+            fn decode(text: &str) -> Option<Self> {
+                text.parse().ok()
+            }
+        }
+    )*}
+}
+
+impl_payload!(bool, char, u8, u16, u32, u64, u128, usize,
+        i8, i16, i32, i64, i128, isize, f32, f64);
+
+/// Table of path nodes that are written to the snapshot. Each node is
+/// written once and other lines refer to it by the number.
+#[derive(Default)]
+struct PathTable {
+    ids: BTreeMap<RcPath, usize>,
+    out: String,
+}
+
+/// State of the network that is being loaded from the snapshot.
+struct Loader<M> {
+    network: Network<M>,
+
+    /// Number of the current line.
+    line: usize,
+
+    /// Path nodes by their numbers.
+    paths: BTreeMap<usize, RcPath>,
+
+    /// Interface which functions and prerequisites are being read.
+    interface: Option<(InterfaceKey, Interface)>,
+
+    /// Process which threads are being read.
+    process: Option<ProcessKey>,
+
+    /// Interface which provided functions are being read.
+    implements: Option<InterfaceKey>,
+
+    /// Thread which wake reason is being read.
+    thread: Option<ThreadKey>,
+
+    /// Channel which messages are being read.
+    channel: Option<ChannelKey>,
+
+    /// Package which policy is being read.
+    policy: Option<RcPath>,
+
+    /// Lines where threads were declared.
+    thread_lines: BTreeMap<ThreadKey, usize>,
+
+    /// Run queue and the line where it was declared.
+    run: Option<(Vec<ThreadKey>, usize)>,
+
+    ended: bool,
+}
+
+impl<M: Payload> Network<M> {
+
+    /// Save the state of the network to the text snapshot.
+    ///
+    /// Snapshot starts with the format version and has one record per
+    /// line. Threads, functions and prerequisites follow the process or
+    /// interface they belong to. Messages follow their channel and
+    /// restrictions follow their policy. Each path
+    /// node is written once and is referred by it's number. Wait map is
+    /// not written as it is rebuilt from the states of the threads.
+    ///
+    /// Scheduler is saved by it's name along with the run queue.
+    /// Schedulers without a name are not saved.
+    pub fn save(&self) -> String {
+        let mut paths = PathTable::default();
+        let mut out = String::new();
+
+        writeln!(out, "clock {}", self.clock.now()).unwrap();
+        writeln!(out, "cpus {}", self.cpus).unwrap();
+        if let Some(name) = self.scheduler.name() {
+            writeln!(out, "scheduler {}", name).unwrap();
+        }
+        writeln!(out, "next-thread {}", self.threads.last_key()).unwrap();
+        writeln!(out, "next-process {}", self.next_process_key).unwrap();
+        writeln!(out, "next-channel {}", self.next_channel_key).unwrap();
+
+        for path in self.packages.leaves() {
+            writeln!(out, "package {}", paths.id(&path)).unwrap();
+        }
+
+        for path in self.packages.walk() {
+            let policy = match self.packages.policy(&path) {
+                Some(policy) => policy,
+                None => continue,
+            };
+            writeln!(out, "policy {}", paths.id(&path)).unwrap();
+            for access in [Access::Implement, Access::Connect] {
+                if let Some(prefixes) = policy.allowed(access) {
+                    write!(out, "restrict {}", access).unwrap();
+                    for prefix in prefixes {
+                        write!(out, " {}", paths.id(prefix)).unwrap();
+                    }
+                    writeln!(out).unwrap();
+                }
+            }
+        }
+
+        for (key, interface) in self.interfaces.interfaces() {
+            writeln!(out, "interface {} {}",
+                    paths.id(key.path()), key.version()).unwrap();
+            for func in interface.fns() {
+                writeln!(out, "fn {}", func_record(func)).unwrap();
+            }
+            for prerequisite in interface.prerequisites() {
+                writeln!(out, "prerequisite {} {}",
+                        paths.id(prerequisite.path()),
+                        prerequisite.version()).unwrap();
+            }
+        }
+
+        for (key, process) in self.processes.processes() {
+            writeln!(out, "process {} {}", key, paths.id(process.path()))
+                    .unwrap();
+            for interface in process.implementations() {
+                writeln!(out, "implements {} {}",
+                        paths.id(interface.path()), interface.version())
+                        .unwrap();
+                let fns = process.fn_implementations(interface);
+                for func in fns.into_iter().flatten() {
+                    writeln!(out, "provides {}", func_record(func)).unwrap();
+                }
+            }
+            for thread_key in process.threads() {
+                // Only registered threads are part of the network.
+                let thread = match self.threads.get(thread_key) {
+                    Some(thread) => thread,
+                    None => continue,
+                };
+                write!(out, "thread {} {} ", thread_key, thread.priority())
+                        .unwrap();
+                match thread.state() {
+                    ThreadState::Sleep => writeln!(out, "sleep"),
+                    ThreadState::Active => writeln!(out, "active"),
+                    ThreadState::WaitWithoutTimeout(chan) =>
+                        writeln!(out, "wait {}", chan),
+                    ThreadState::WaitWithTimeout(chan, deadline) =>
+                        writeln!(out, "wait {} {}", chan, deadline),
+                }.unwrap();
+
+                match thread.wake_reason() {
+                    Some(WakeReason::Signal(chan)) =>
+                        writeln!(out, "wake signal {}", chan).unwrap(),
+                    Some(WakeReason::Closed(chan)) =>
+                        writeln!(out, "wake closed {}", chan).unwrap(),
+                    Some(WakeReason::TimedOut(chan)) =>
+                        writeln!(out, "wake timeout {}", chan).unwrap(),
+                    None => (),
+                }
+            }
+        }
+
+        for (key, channel) in self.channels.iter() {
+            write!(out, "channel {} {}", key, channel.capacity()).unwrap();
+            for participant in channel.participants() {
+                write!(out, " {}", participant).unwrap();
+            }
+            writeln!(out).unwrap();
+
+            for message in channel.queue() {
+                writeln!(out, "message {}", quote(&message.encode())).unwrap();
+            }
+            for (sender, message) in channel.pending() {
+                writeln!(out, "pending {} {}",
+                        sender, quote(&message.encode())).unwrap();
+            }
+            for receiver in channel.receivers() {
+                writeln!(out, "receiver {}", receiver).unwrap();
+            }
+        }
+
+        write!(out, "run").unwrap();
+        for thread in self.scheduler.queue() {
+            write!(out, " {}", thread).unwrap();
+        }
+        writeln!(out).unwrap();
+        writeln!(out, "end").unwrap();
+
+        format!("{} {}\n{}{}", MAGIC, FORMAT_VERSION, paths.out, out)
+    }
+
+    /// Load the network from the snapshot that was created by `save`.
+    /// Loaded network uses the saved scheduler with the saved run queue.
+    /// Round-robin scheduler is used if snapshot names no scheduler.
+    ///
+    /// # Errors
+    /// `BadSnapshot` is returned if snapshot is malformed, has unsupported
+    /// format version or describes inconsistent network.
+    pub fn load(text: &str) -> Result<Self, Error> {
+        let mut loader = Loader::new();
+        let mut header = false;
+
+        for (i, line) in text.lines().enumerate() {
+            loader.line = i + 1;
+            let tokens = match tokenize(line) {
+                Ok(tokens) => tokens,
+                Err(reason) => return Err(loader.error(reason)),
+            };
+            if tokens.is_empty() {
+                continue;
+            }
+            if loader.ended {
+                return Err(loader.error("record after the end"));
+            }
+
+            if !header {
+                if tokens[0] != MAGIC || tokens.len() != 2 {
+                    return Err(loader.error("not a network snapshot"));
+                }
+                let version: u32 = loader.parse(&tokens[1], "format version")?;
+                if version != FORMAT_VERSION {
+                    return Err(loader.error(format!(
+                            "unsupported format version {}", version)));
+                }
+                header = true;
+                continue;
+            }
+
+            loader.record(&tokens)?;
+        }
+
+        if !loader.ended {
+            return Err(loader.error("unexpected end of snapshot"));
+        }
+        loader.finish()
+    }
+}
+
+impl PathTable {
+
+    /// Number of the path node. Node and all it's parents are written
+    /// to the table if they are not there yet.
+    fn id(&mut self, path: &RcPath) -> usize {
+        if let Some(id) = self.ids.get(path) {
+            return *id;
+        }
+
+        let mut iter = PathIter::new(path.clone());
+        iter.next_back();
+        let parent = match iter.next_back() {
+            Some(parent) => self.id(&parent).to_string(),
+            None => "-".to_string(),
+        };
+
+        let id = self.ids.len();
+        writeln!(self.out, "path {} {} {}", id, parent, quote(path.name()))
+                .unwrap();
+        self.ids.insert(path.clone(), id);
+        id
+    }
+}
+
+impl<M: Payload> Loader<M> {
+
+    fn new() -> Self {
+        Loader {
+            network: Default::default(),
+            line: 0,
+            paths: Default::default(),
+            interface: None,
+            process: None,
+            implements: None,
+            thread: None,
+            channel: None,
+            policy: None,
+            thread_lines: Default::default(),
+            run: None,
+            ended: false,
+        }
+    }
+
+    /// Error at the current line.
+    fn error<S: Into<String>>(&self, reason: S) -> Error {
+        Error::BadSnapshot(self.line, reason.into())
+    }
+
+    fn parse<T: FromStr>(&self, token: &str, what: &str) -> Result<T, Error> {
+        match token.parse() {
+            Ok(value) => Ok(value),
+            Err(_) => Err(self.error(format!("bad {} '{}'", what, token))),
+        }
+    }
+
+    fn version(&self, token: &str) -> Result<Version, Error> {
+        match token.parse() {
+            Ok(version) => Ok(version),
+            Err(e) => Err(self.error(e.to_string())),
+        }
+    }
+
+    /// Function from the name, version and optional signature.
+    fn func(&self, tokens: &[String]) -> Result<InterfaceFunc, Error> {
+        self.args(tokens, 2, 3)?;
+        let version = self.version(&tokens[2])?;
+        let signature = match tokens.get(3) {
+            Some(token) => match token.parse::<Signature>() {
+                Ok(signature) => signature,
+                Err(e) => return Err(self.error(e.to_string())),
+            },
+            None => Signature::default(),
+        };
+        Ok(InterfaceFunc::with_signature(tokens[1].clone(), version,
+                signature))
+    }
+
+    fn path(&self, token: &str) -> Result<RcPath, Error> {
+        let id: usize = self.parse(token, "path number")?;
+        match self.paths.get(&id) {
+            Some(path) => Ok(path.clone()),
+            None => Err(self.error(format!("unknown path {}", id))),
+        }
+    }
+
+    fn thread(&self, token: &str) -> Result<ThreadKey, Error> {
+        let key = self.parse(token, "thread key")?;
+        if self.network.threads.get(&key).is_none() {
+            return Err(self.error(format!("unknown thread {}", key)));
+        }
+        Ok(key)
+    }
+
+    fn payload(&self, token: &str) -> Result<M, Error> {
+        match M::decode(token) {
+            Some(message) => Ok(message),
+            None => Err(self.error(format!("bad message '{}'", token))),
+        }
+    }
+
+    /// Check the number of arguments of the record.
+    fn args(&self, tokens: &[String], min: usize, max: usize)
+            -> Result<(), Error> {
+        let count = tokens.len() - 1;
+        if count < min || count > max {
+            return Err(self.error(format!(
+                    "wrong number of arguments for '{}'", tokens[0])));
+        }
+        Ok(())
+    }
+
+    /// Save the interface which records were read.
+    fn flush_interface(&mut self) -> Result<(), Error> {
+        if let Some((key, interface)) = self.interface.take() {
+            if let Err(e) = self.network.add_interface(key, interface) {
+                return Err(self.error(e.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Read one record of the snapshot.
+    fn record(&mut self, tokens: &[String]) -> Result<(), Error> {
+        let record = tokens[0].as_str();
+        match record {
+            "fn" | "prerequisite" | "implements" | "provides" | "thread"
+                | "wake" | "restrict"
+                | "message" | "pending" | "receiver" => (),
+            _ => {
+                // Record that does not belong to the previous one.
+                self.flush_interface()?;
+                self.process = None;
+                self.implements = None;
+                self.thread = None;
+                self.channel = None;
+                self.policy = None;
+            },
+        }
+
+        match record {
+            "clock" => {
+                self.args(tokens, 1, 1)?;
+                let now = self.parse(&tokens[1], "time")?;
+                self.network.clock.advance(now);
+            },
+            "cpus" => {
+                self.args(tokens, 1, 1)?;
+                let cpus = self.parse(&tokens[1], "processor count")?;
+                if cpus == 0 {
+                    return Err(self.error("no processors"));
+                }
+                self.network.cpus = cpus;
+            },
+            "scheduler" => {
+                self.args(tokens, 1, 1)?;
+                self.network.scheduler = match tokens[1].as_str() {
+                    "round-robin" => Box::new(RoundRobin::new()),
+                    "priority" => Box::new(PriorityScheduler::new()),
+                    other => return Err(self.error(format!(
+                            "unknown scheduler '{}'", other))),
+                };
+            },
+            "next-thread" => {
+                self.args(tokens, 1, 1)?;
+                let key = self.parse(&tokens[1], "thread key")?;
+                self.network.threads.set_last_key(key);
+            },
+            "next-process" => {
+                self.args(tokens, 1, 1)?;
+                self.network.next_process_key =
+                        self.parse(&tokens[1], "process key")?;
+            },
+            "next-channel" => {
+                self.args(tokens, 1, 1)?;
+                self.network.next_channel_key =
+                        self.parse(&tokens[1], "channel key")?;
+            },
+            "path" => {
+                self.args(tokens, 3, 3)?;
+                let id = self.parse(&tokens[1], "path number")?;
+                if self.paths.contains_key(&id) {
+                    return Err(self.error(format!(
+                            "path {} is already declared", id)));
+                }
+                let name = tokens[3].clone();
+                let path = if tokens[2] == "-" {
+                    Path::new(name)
+                } else {
+                    Path::new_from_parent(self.path(&tokens[2])?, name)
+                };
+                self.paths.insert(id, path);
+            },
+            "policy" => {
+                self.args(tokens, 1, 1)?;
+                let path = self.path(&tokens[1])?;
+                self.network.set_policy(&path, Policy::new());
+                self.policy = Some(path);
+            },
+            "restrict" => {
+                self.args(tokens, 1, usize::MAX)?;
+                let access = match tokens[1].as_str() {
+                    "implement" => Access::Implement,
+                    "connect" => Access::Connect,
+                    other => return Err(self.error(format!(
+                            "unknown access '{}'", other))),
+                };
+                let path = match self.policy {
+                    Some(ref path) => path.clone(),
+                    None => return Err(self.error("restriction out of policy")),
+                };
+                let mut policy = self.network.packages.policy(&path).unwrap()
+                        .clone();
+                policy.restrict(access);
+                for token in tokens[2..].iter() {
+                    policy.allow(access, self.path(token)?);
+                }
+                self.network.set_policy(&path, policy);
+            },
+            "package" => {
+                self.args(tokens, 1, 1)?;
+                let path = self.path(&tokens[1])?;
+                self.network.packages.store_path(&path);
+            },
+            "interface" => {
+                self.args(tokens, 2, 2)?;
+                let key = InterfaceKey::new(self.path(&tokens[1])?,
+                        self.version(&tokens[2])?);
+                self.interface = Some((key, Interface::new()));
+            },
+            "fn" => {
+                let func = self.func(tokens)?;
+                match self.interface {
+                    Some((_, ref mut interface)) => interface.add_fn(func),
+                    None => return Err(self.error("function out of interface")),
+                }
+            },
+            "prerequisite" => {
+                self.args(tokens, 2, 2)?;
+                let key = InterfaceKey::new(self.path(&tokens[1])?,
+                        self.version(&tokens[2])?);
+                match self.interface {
+                    Some((_, ref mut interface)) =>
+                        interface.add_prerequisite(key),
+                    None => return Err(self.error(
+                            "prerequisite out of interface")),
+                }
+            },
+            "process" => {
+                self.args(tokens, 2, 2)?;
+                let key = self.parse(&tokens[1], "process key")?;
+                let path = self.path(&tokens[2])?;
+                if self.network.processes.insert(key, Process::new(path.clone()))
+                        .is_err() {
+                    return Err(self.error(format!(
+                            "process {} is already declared", key)));
+                }
+                self.network.packages.add_process(&path, key);
+                self.process = Some(key);
+            },
+            "implements" => {
+                self.args(tokens, 2, 2)?;
+                let key = InterfaceKey::new(self.path(&tokens[1])?,
+                        self.version(&tokens[2])?);
+                let process = match self.process {
+                    Some(process) => process,
+                    None => return Err(self.error(
+                            "implementation out of process")),
+                };
+                self.network.processes.get_mut(&process).unwrap()
+                        .add_implementation(key.clone());
+                self.implements = Some(key);
+            },
+            "provides" => {
+                let func = self.func(tokens)?;
+                let (process, key) = match (self.process, &self.implements) {
+                    (Some(process), Some(key)) => (process, key.clone()),
+                    _ => return Err(self.error(
+                            "function out of implementation")),
+                };
+                self.network.processes.get_mut(&process).unwrap()
+                        .add_fn_implementation(key, func);
+            },
+            "thread" => self.thread_record(tokens)?,
+            "wake" => {
+                self.args(tokens, 2, 2)?;
+                let chan = self.parse(&tokens[2], "channel key")?;
+                let reason = match tokens[1].as_str() {
+                    "signal" => WakeReason::Signal(chan),
+                    "closed" => WakeReason::Closed(chan),
+                    "timeout" => WakeReason::TimedOut(chan),
+                    other => return Err(self.error(format!(
+                            "unknown wake reason '{}'", other))),
+                };
+                match self.thread {
+                    Some(thread) => self.network.threads.get_mut(&thread)
+                            .unwrap().set_wake_reason(reason),
+                    None => return Err(self.error("wake reason out of thread")),
+                }
+            },
+            "channel" => self.channel_record(tokens)?,
+            "message" | "pending" | "receiver" => {
+                let key = match self.channel {
+                    Some(key) => key,
+                    None => return Err(self.error("message out of channel")),
+                };
+
+                if record == "message" {
+                    self.args(tokens, 1, 1)?;
+                    let message = self.payload(&tokens[1])?;
+                    self.network.channels.get_mut(&key).unwrap().push(message);
+                    return Ok(());
+                }
+
+                let (thread, message) = if record == "pending" {
+                    self.args(tokens, 2, 2)?;
+                    (self.thread(&tokens[1])?, Some(self.payload(&tokens[2])?))
+                } else {
+                    self.args(tokens, 1, 1)?;
+                    (self.thread(&tokens[1])?, None)
+                };
+
+                let channel = self.network.channels.get_mut(&key).unwrap();
+                if !channel.participants().contains(&thread) {
+                    return Err(Error::BadSnapshot(self.line, format!(
+                            "thread {} does not participate in channel {}",
+                            thread, key)));
+                }
+                match message {
+                    Some(message) => channel.push_pending(thread, message),
+                    None => channel.push_receiver(thread),
+                }
+            },
+            "run" => {
+                if self.run.is_some() {
+                    return Err(self.error("run queue is already declared"));
+                }
+                let mut run = Vec::new();
+                for token in tokens[1..].iter() {
+                    run.push(self.thread(token)?);
+                }
+                self.run = Some((run, self.line));
+            },
+            "end" => {
+                self.args(tokens, 0, 0)?;
+                self.ended = true;
+            },
+            other => return Err(self.error(format!(
+                    "unknown record '{}'", other))),
+        }
+        Ok(())
+    }
+
+    /// Read the thread of the current process.
+    fn thread_record(&mut self, tokens: &[String]) -> Result<(), Error> {
+        self.args(tokens, 3, 5)?;
+        let process = match self.process {
+            Some(process) => process,
+            None => return Err(self.error("thread out of process")),
+        };
+
+        let key = self.parse(&tokens[1], "thread key")?;
+        let mut thread = Thread::new();
+        thread.set_priority(self.parse(&tokens[2], "priority")?);
+
+        let state = match (tokens[3].as_str(), tokens.len()) {
+            ("sleep", 4) => ThreadState::Sleep,
+            ("active", 4) => ThreadState::Active,
+            ("wait", 5) => ThreadState::WaitWithoutTimeout(
+                    self.parse(&tokens[4], "channel key")?),
+            ("wait", 6) => ThreadState::WaitWithTimeout(
+                    self.parse(&tokens[4], "channel key")?,
+                    self.parse(&tokens[5], "time")?),
+            _ => return Err(self.error("bad thread state")),
+        };
+        thread.set_state(state);
+
+        if !self.network.threads.insert(key, thread) {
+            return Err(self.error(format!(
+                    "thread {} is already declared", key)));
+        }
+        self.network.processes.get_mut(&process).unwrap().attach_thread(key);
+        self.thread_lines.insert(key, self.line);
+        self.thread = Some(key);
+        Ok(())
+    }
+
+    /// Read the channel and it's participants.
+    fn channel_record(&mut self, tokens: &[String]) -> Result<(), Error> {
+        self.args(tokens, 2, usize::MAX)?;
+        let key = self.parse(&tokens[1], "channel key")?;
+        let capacity = self.parse(&tokens[2], "capacity")?;
+        if capacity == 0 {
+            return Err(self.error("channel capacity is zero"));
+        }
+
+        let mut participants = BTreeSet::new();
+        for token in tokens[3..].iter() {
+            participants.insert(self.thread(token)?);
+        }
+
+        let mut channel = match participants.iter().next() {
+            Some(first) => Channel::with_capacity(*first, capacity),
+            None => return Err(self.error("channel has no participants")),
+        };
+        for participant in participants.iter() {
+            channel.add_participant(*participant);
+        }
+
+        if self.network.channels.insert(key, channel) {
+            return Err(self.error(format!(
+                    "channel {} is already declared", key)));
+        }
+        self.network.wait_deps.add_channel(key, Default::default());
+        for participant in participants {
+            self.network.threads.get_mut(&participant).unwrap()
+                    .channels_mut().insert(key);
+        }
+
+        self.channel = Some(key);
+        Ok(())
+    }
+
+    /// Check the loaded network and rebuild the wait map and the run
+    /// queue.
+    fn finish(mut self) -> Result<Network<M>, Error> {
+        let network = &mut self.network;
+
+        let last_thread = network.threads.map().keys().next_back().cloned();
+        let last_process = network.processes.processes().keys()
+                .next_back().cloned();
+        let last_channel = network.channels.iter().map(|(k, _)| *k)
+                .next_back();
+        if matches!(last_thread, Some(k) if k > network.threads.last_key())
+                || matches!(last_process,
+                    Some(k) if k >= network.next_process_key)
+                || matches!(last_channel,
+                    Some(k) if k >= network.next_channel_key) {
+            return Err(Error::BadSnapshot(self.line,
+                    "key counter is behind the keys in use".to_string()));
+        }
+
+        // Threads may wait only for the channels where they participate.
+        let mut waiters = Vec::new();
+        for (key, thread) in network.threads.iter() {
+            let line = self.thread_lines[key];
+            let chan = match thread.state() {
+                ThreadState::WaitWithoutTimeout(chan) => {
+                    waiters.push((*key, *chan));
+                    chan
+                },
+                ThreadState::WaitWithTimeout(chan, _) => chan,
+                _ => continue,
+            };
+            if !thread.channels().contains(chan) {
+                return Err(Error::BadSnapshot(line, format!(
+                        "thread {} waits for channel {} where it does not \
+                        participate", key, chan)));
+            }
+        }
+
+        for (thread, chan) in waiters {
+            network.wait_deps.add_waiter(chan, thread).unwrap();
+        }
+        // Deadlocks are restored as they were, see `Network::analyze`.
+        let channels: Vec<ChannelKey> = network.channels.iter()
+                .map(|(key, _)| *key)
+                .collect();
+        for chan in channels.iter() {
+            network.restore_channel_relations(chan);
+        }
+
+        let active: Vec<ThreadKey> = network.threads.iter()
+                .filter(|(_, t)| *t.state() == ThreadState::Active)
+                .map(|(k, _)| *k)
+                .collect();
+        if active.len() > network.cpus {
+            return Err(Error::BadSnapshot(self.line, format!(
+                    "{} active threads on {} processors",
+                    active.len(), network.cpus)));
+        }
+        network.running.extend(active);
+
+        // Scheduler needs priorities of the threads before they are queued
+        // to keep the saved order.
+        let threads = network.threads.map().keys().cloned().collect();
+        network.inherit_priorities(&threads);
+        for (key, thread) in network.threads.iter() {
+            network.scheduler.set_priority(key, thread.effective_priority());
+        }
+
+        // Run queue must contain exactly the sleeping threads.
+        let (run, line) = match self.run {
+            Some(run) => run,
+            None => return Err(Error::BadSnapshot(self.line,
+                    "run queue is missing".to_string())),
+        };
+        for thread in run.iter() {
+            if *network.threads.get(thread).unwrap().state()
+                    != ThreadState::Sleep {
+                return Err(Error::BadSnapshot(line, format!(
+                        "thread {} in run queue is not sleeping", thread)));
+            }
+            network.scheduler.push(*thread);
+        }
+        if network.scheduler.len() != network.threads.iter()
+                .filter(|(_, t)| *t.state() == ThreadState::Sleep).count() {
+            return Err(Error::BadSnapshot(line,
+                    "sleeping thread is missing in run queue".to_string()));
+        }
+        Ok(self.network)
+    }
+}
+
+/// Name, version and signature of the function. Signature is omitted if
+/// it is the default one.
+fn func_record(func: &InterfaceFunc) -> String {
+    let mut record = format!("{} {}", quote(func.name()), func.version());
+    if *func.signature() != Signature::default() {
+        record.push(' ');
+        record.push_str(&quote(&func.signature().to_string()));
+    }
+    record
+}
+
+/// Put text in quotes and escape special characters.
+fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Split line into words. Quoted words may contain spaces and escaped
+/// characters.
+fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while matches!(chars.peek(), Some(c) if c.is_whitespace()) {
+            chars.next();
+        }
+        let first = match chars.peek() {
+            Some(c) => *c,
+            None => break,
+        };
+
+        let mut token = String::new();
+        if first == '"' {
+            chars.next();
+            loop {
+                let c = match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        _ => return Err("bad escape sequence".to_string()),
+                    },
+                    Some(c) => c,
+                    None => return Err("unterminated string".to_string()),
+                };
+                token.push(c);
+            }
+            if matches!(chars.peek(), Some(c) if !c.is_whitespace()) {
+                return Err("no space after string".to_string());
+            }
+        } else {
+            while let Some(c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                token.push(*c);
+                chars.next();
+            }
+        }
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use crate::PriorityScheduler;
+
+    fn sample() -> (Network<String>, ThreadKey, ThreadKey, ChannelKey) {
+        let mut network: Network<String> = Network::default();
+        let root = Path::new("root".to_string());
+        let a = Path::new_from_parent(root.clone(), "a b".to_string());
+        let b = Path::new_from_parent(root.clone(), "b".to_string());
+
+        let ik = InterfaceKey::new(a.clone(), Version::new(1, 2, 3));
+        let mut i = Interface::new();
+        i.add_fn(InterfaceFunc::new("f".to_string(), Version::new(1, 0, 0)));
+        i.add_fn(InterfaceFunc::with_signature("g".to_string(),
+                Version::new(1, 0, 0), "(s: str) -> chan".parse().unwrap()));
+        i.add_prerequisite(InterfaceKey::new(b.clone(), Version::new(1, 0, 0)));
+        network.add_interface(ik.clone(), i).unwrap();
+
+        let mut process = Process::new(a);
+        process.add_fn_implementation(ik,
+                InterfaceFunc::new("f".to_string(), Version::new(1, 0, 0)));
+        let proc1 = network.new_process(process).unwrap();
+        let proc2 = network.new_process(Process::new(b.clone())).unwrap();
+
+        let th1 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th2 = network.new_thread(Thread::new(), &proc2).unwrap();
+        let mut thread = Thread::new();
+        thread.set_priority(3);
+        let th3 = network.new_thread(thread, &proc2).unwrap();
+
+        let mut ch = Channel::with_capacity(th1, 1);
+        ch.add_participant(th2);
+        let ch = network.new_channel(ch).unwrap();
+        let mut ch2 = Channel::new(th2);
+        ch2.add_participant(th3);
+        let ch2 = network.new_channel(ch2).unwrap();
+        let ch3 = network.new_channel(Channel::new(th1)).unwrap();
+
+        // Channels above were opened before connections were restricted.
+        let mut policy = Policy::new();
+        policy.allow(Access::Implement, root.clone());
+        policy.restrict(Access::Connect);
+        network.set_policy(&root, policy);
+        network.set_policy(&b, Policy::new());
+
+        network.advance_time(7);
+        network.send(&th2, &ch, "one \"quoted\"\nline".to_string()).unwrap();
+        network.send(&th2, &ch, "two".to_string()).unwrap();
+        network.wait_thread(&th3, &ch2, None).unwrap();
+        network.wait_thread(&th1, &ch3, Some(5)).unwrap();
+        network.close_channel(&ch3).unwrap();
+        (network, th1, th2, ch)
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let (network, th1, th2, ch) = sample();
+        let text = network.save();
+        let loaded: Network<String> = Network::load(&text).unwrap();
+        assert_eq!(loaded.save(), text);
+
+        assert_eq!(loaded.clock().now(), 7);
+        let thread = loaded.thread(&th2).unwrap();
+        assert!(*thread.state() == ThreadState::WaitWithoutTimeout(ch));
+        assert_eq!(thread.inherited_priority(), Some(3));
+        assert_eq!(loaded.thread(&(th2 + 1)).unwrap().priority(), 3);
+        assert_eq!(loaded.thread(&th1).unwrap().wake_reason(),
+                Some(&WakeReason::Closed(ch + 2)));
+
+        let channel = loaded.channels().get(&ch).unwrap();
+        assert_eq!(channel.queue().front().unwrap(), "one \"quoted\"\nline");
+        assert_eq!(channel.pending().front().unwrap().1, "two");
+
+        // Wait map is rebuilt.
+        assert!(loaded.wait_deps().channel_wait_map()[&ch].contains(&th2));
+        assert!(loaded.wait_deps().relation_causes(&ch, &(ch + 1)).is_some());
+
+        // Paths share their common parent.
+        let paths: Vec<RcPath> = loaded.processes().processes().values()
+                .map(|p| PathIter::new(p.path().clone()).next().unwrap())
+                .collect();
+        assert!(Rc::ptr_eq(&*paths[0], &*paths[1]));
+        let key = loaded.interfaces().interfaces().keys().next().unwrap();
+        let process = loaded.processes().get(&0).unwrap();
+        assert!(Rc::ptr_eq(key.path(), process.path()));
+        assert_eq!(process.fn_implementations(key).unwrap().len(), 1);
+        let policy = loaded.packages().policy(&"root".parse().unwrap())
+                .unwrap();
+        assert_eq!(policy.allowed(Access::Implement).unwrap().len(), 1);
+        assert!(policy.allowed(Access::Connect).unwrap().is_empty());
+
+        let entries = loaded.packages().entries(process.path()).unwrap();
+        assert!(entries.processes().contains(&0));
+        assert!(entries.interfaces().contains(key));
+        let interface = loaded.interfaces().interface(key).unwrap();
+        let func = interface.fns().iter().find(|f| f.name() == "g").unwrap();
+        assert_eq!(func.signature().to_string(), "(s: str) -> chan");
+    }
+
+    #[test]
+    fn snapshot_run_queue() {
+        let mut network = Network::new();
+        network.set_scheduler(Box::new(PriorityScheduler::new()));
+        let proc1 = network.new_process(Process::new(Path::new("a".to_string())))
+                .unwrap();
+        let th1 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th2 = network.new_thread(Thread::new(), &proc1).unwrap();
+        network.set_priority(&th2, 1).unwrap();
+        network.set_cpu_count(2);
+
+        let mut loaded: Network = Network::load(&network.save()).unwrap();
+        assert_eq!(loaded.save(), network.save());
+        assert_eq!(loaded.scheduler().name(), Some("priority"));
+        assert_eq!(loaded.cpu_count(), 2);
+        assert_eq!(loaded.new_thread(Thread::new(), &proc1), Ok(th2 + 1));
+        assert_eq!(loaded.new_process(Process::new(Path::new("b".to_string()))),
+                Ok(proc1 + 1));
+
+        // Loaded scheduler still runs threads by priority.
+        loaded.set_priority(&(th2 + 1), 2).unwrap();
+        assert_eq!(loaded.schedule(), Some(th2 + 1));
+        assert_eq!(loaded.schedule(), Some(th2));
+        assert_eq!(loaded.scheduler().queue(), vec![th1]);
+    }
+
+    #[test]
+    fn snapshot_deadlock() {
+        let mut network: Network = Network::new();
+        let proc1 = network.new_process(Process::new(Path::new("a".to_string())))
+                .unwrap();
+        let th1 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th2 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th3 = network.new_thread(Thread::new(), &proc1).unwrap();
+        let th4 = network.new_thread(Thread::new(), &proc1).unwrap();
+
+        let mut ch12 = Channel::new(th1);
+        ch12.add_participant(th2);
+        let mut ch23 = Channel::new(th2);
+        ch23.add_participant(th3);
+        let mut ch31 = Channel::new(th3);
+        ch31.add_participant(th1);
+        ch31.add_participant(th4);
+        let ch12 = network.new_channel(ch12).unwrap();
+        let ch23 = network.new_channel(ch23).unwrap();
+        let ch31 = network.new_channel(ch31).unwrap();
+
+        network.wait_thread(&th1, &ch12, None).unwrap();
+        network.wait_thread(&th2, &ch23, None).unwrap();
+        network.wait_thread(&th3, &ch31, None).unwrap();
+
+        // Removed thread was the only one to signal the loop of channels.
+        network.remove_thread(&th4).unwrap();
+        assert_eq!(network.analyze().deadlocks().len(), 1);
+
+        let loaded: Network = Network::load(&network.save()).unwrap();
+        assert_eq!(loaded.save(), network.save());
+        let analysis = loaded.analyze();
+        assert_eq!(analysis, network.analyze());
+        let deadlock = &analysis.deadlocks()[0];
+        assert_eq!(deadlock.channels().iter().cloned().collect::<Vec<_>>(),
+                vec![ch12, ch23, ch31]);
+        assert_eq!(deadlock.threads().iter().cloned().collect::<Vec<_>>(),
+                vec![th1, th2, th3]);
+    }
+
+    #[test]
+    fn snapshot_errors() {
+        let (network, _, _, _) = sample();
+        let text = network.save();
+
+        let result: Result<Network<String>, _> = Network::load("");
+        assert_eq!(result.err(),
+                Some(Error::BadSnapshot(0, "unexpected end of snapshot".into())));
+
+        let result: Result<Network<String>, _> =
+                Network::load(&text.replace("ccs-snapshot 1", "ccs-snapshot 9"));
+        assert_eq!(result.err(), Some(Error::BadSnapshot(1,
+                "unsupported format version 9".into())));
+
+        // Payload type does not match.
+        let result: Result<Network<u32>, _> = Network::load(&text);
+        match result {
+            Err(Error::BadSnapshot(_, reason)) =>
+                assert!(reason.starts_with("bad message")),
+            _ => panic!("payload was not checked"),
+        }
+
+        let broken = text.replace("wait 0\n", "wait 5\n");
+        let result: Result<Network<String>, _> = Network::load(&broken);
+        assert!(result.is_err());
+
+        let broken = text.replace("end\n", "");
+        let result: Result<Network<String>, _> = Network::load(&broken);
+        assert!(result.is_err());
+
+        let broken = text.replace("scheduler round-robin", "scheduler fifo");
+        let result: Result<Network<String>, _> = Network::load(&broken);
+        match result {
+            Err(Error::BadSnapshot(_, reason)) =>
+                assert_eq!(reason, "unknown scheduler 'fifo'"),
+            _ => panic!("scheduler was not checked"),
+        }
+
+        let broken = text.replace("\nrun", "\nchannel 9 4\nrun");
+        let result: Result<Network<String>, _> = Network::load(&broken);
+        match result {
+            Err(Error::BadSnapshot(_, reason)) =>
+                assert_eq!(reason, "channel has no participants"),
+            _ => panic!("empty channel was not rejected"),
+        }
+
+        let mut network: Network = Network::new();
+        network.set_cpu_count(2);
+        let proc1 = network.new_process(Process::new(Path::new("a".to_string())))
+                .unwrap();
+        network.new_thread(Thread::new(), &proc1).unwrap();
+        network.new_thread(Thread::new(), &proc1).unwrap();
+        network.schedule().unwrap();
+        network.schedule().unwrap();
+        let broken = network.save().replace("cpus 2", "cpus 1");
+        let result: Result<Network, _> = Network::load(&broken);
+        match result {
+            Err(Error::BadSnapshot(_, reason)) =>
+                assert_eq!(reason, "2 active threads on 1 processors"),
+            _ => panic!("processor count was not checked"),
+        }
+    }
+}
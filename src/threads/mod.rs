@@ -3,6 +3,8 @@ use std::collections::btree_map::Iter;
 
 use crate::{
     ChannelKey,
+    Priority,
+    Time,
 };
 
 /// Type used to identify unique threads.
@@ -15,8 +17,9 @@ pub enum State {
     /// Thread is waiting for external event without timeout.
     WaitWithoutTimeout(ChannelKey),
 
-    /// Thread is waiting for external event with timeout.
-    WaitWithTimeout(ChannelKey),
+    /// Thread is waiting for external event with timeout. Contains the time
+    /// when the timeout expires.
+    WaitWithTimeout(ChannelKey, Time),
 
     /// Thread actively runs.
     Active,
@@ -25,6 +28,20 @@ pub enum State {
     Sleep,
 }
 
+/// Reason why thread has left the waiting state.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WakeReason {
+
+    /// Signal was received from the channel.
+    Signal(ChannelKey),
+
+    /// Channel was closed while thread was waiting for it's signal.
+    Closed(ChannelKey),
+
+    /// Timeout expired before the signal from the channel was received.
+    TimedOut(ChannelKey),
+}
+
 /// Thread related metadata. Does not contain architecture-specific
 /// information. This information is stored in another struct implemented
 /// by the OS core threading module that is connected to this structure
@@ -32,9 +49,16 @@ pub enum State {
 pub struct Thread {
     state: State,
     chans: BTreeSet<ChannelKey>,
+    wake_reason: Option<WakeReason>,
+    priority: Priority,
+
+    /// Priority that is temporarily lent to this thread by the threads
+    /// that wait for it's signal.
+    inherited: Option<Priority>,
 }
 
 /// Thread set. Allows to add, remove and search for threads.
+#[derive(Default)]
 pub struct Set {
     map: BTreeMap<Key, Thread>,
 
@@ -54,27 +78,67 @@ impl Thread {
     }
 
     /// Set new thread state.
-    pub fn set_state(&mut self, new_state: State) {
+    pub(crate) fn set_state(&mut self, new_state: State) {
         self.state = new_state;
     }
 
+    /// Reason of the last wake up from the waiting state. None if thread
+    /// has never been waiting.
+    pub fn wake_reason(&self) -> Option<&WakeReason> {
+        self.wake_reason.as_ref()
+    }
+
+    /// Set reason of the last wake up from the waiting state.
+    pub(crate) fn set_wake_reason(&mut self, reason: WakeReason) {
+        self.wake_reason = Some(reason);
+    }
+
     /// Channels where this thread participates.
     pub fn channels(&self) -> &BTreeSet<ChannelKey> {
         &self.chans
     }
 
     /// Channels where this thread participates.
-    pub fn channels_mut(&mut self) -> &mut BTreeSet<ChannelKey> {
+    pub(crate) fn channels_mut(&mut self) -> &mut BTreeSet<ChannelKey> {
         &mut self.chans
     }
 
+    /// Own priority of the thread.
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    /// Set own priority of the thread.
+    pub(crate) fn set_priority(&mut self, priority: Priority) {
+        self.priority = priority;
+    }
+
+    /// Priority inherited from the threads that wait for a signal from
+    /// this thread. None if no thread waits for it.
+    pub fn inherited_priority(&self) -> Option<Priority> {
+        self.inherited
+    }
+
+    pub(crate) fn set_inherited_priority(&mut self, priority: Option<Priority>) {
+        self.inherited = priority;
+    }
+
+    /// Priority that the scheduler uses for this thread. It is the greatest
+    /// of own and inherited priorities.
+    pub fn effective_priority(&self) -> Priority {
+        match self.inherited {
+            Some(inherited) if inherited > self.priority => inherited,
+            _ => self.priority,
+        }
+    }
+
     /// Check whether this thread is waiting for given channel.
     pub fn is_waiting_channel(&self, channel: &ChannelKey) -> bool {
         use self::State::*;
 
         match self.state {
             WaitWithoutTimeout(chan) => *channel == chan,
-            WaitWithTimeout(chan)    => *channel == chan,
+            WaitWithTimeout(chan, _) => *channel == chan,
             _                        => false,
         }
     }
@@ -86,6 +150,9 @@ impl Default for Thread {
         Thread {
             state: State::Sleep,
             chans: Default::default(),
+            wake_reason: None,
+            priority: 0,
+            inherited: None,
         }
     }
 }
@@ -104,6 +171,25 @@ impl Set {
         new_key
     }
 
+    /// Add thread to the set with given key. Returns false and does not
+    /// change the set if the key is taken.
+    pub(crate) fn insert(&mut self, key: Key, thread: Thread) -> bool {
+        if self.map.contains_key(&key) {
+            return false;
+        }
+        self.map.insert(key, thread);
+        true
+    }
+
+    /// The last key that was given to a thread.
+    pub(crate) fn last_key(&self) -> Key {
+        self.last_key
+    }
+
+    pub(crate) fn set_last_key(&mut self, key: Key) {
+        self.last_key = key;
+    }
+
     /// Generate new unique key for storing new thread.
     fn generate_new_key(&mut self) -> Key {
         let new_key = self.last_key + 1;
@@ -128,7 +214,7 @@ impl Set {
     }
 
     /// Iterator over elements of the set.
-    pub fn iter(&self) -> Iter<Key, Thread> {
+    pub fn iter(&self) -> Iter<'_, Key, Thread> {
         self.map.iter()
     }
 
@@ -138,16 +224,6 @@ impl Set {
     }
 }
 
-impl Default for Set {
-
-    fn default() -> Self {
-        Set {
-            map: Default::default(),
-            last_key: 0,
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -161,8 +237,8 @@ mod tests {
         let k1 = set.add(thr1);
         let k2 = set.add(thr2);
 
-        { let thr1 = set.get(&k1).unwrap(); }
-        { let thr2 = set.get(&k2).unwrap(); }
+        assert!(set.get(&k1).is_some());
+        assert!(set.get(&k2).is_some());
 
         assert!(set.get(&(k2 + 1)).is_none());
 
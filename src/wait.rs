@@ -1,9 +1,12 @@
+use std::cell::RefCell;
 use std::collections::{BTreeMap, BTreeSet, LinkedList};
-use std::rc::Rc;
+use std::collections::btree_map::Entry;
+use std::rc::{Rc, Weak};
 
 use crate::{
     ThreadKey,
     ChannelKey,
+    Error,
 };
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -32,10 +35,42 @@ pub struct WaitMap {
     /// channel.
     chan_to_graph: BTreeMap<ChannelKey, Rc<GraphNode>>,
 
+    /// Threads that cause each relation between channels. Key is the pair
+    /// of `from` and `to` channels of the relation.
+    causes: BTreeMap<(ChannelKey, ChannelKey), BTreeSet<ThreadKey>>,
+
     /// The graph of dependencies.
     graph: Graph,
 }
 
+/// Loop of channel relations that forms a deadlock.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cycle {
+
+    /// Channels in the loop. Each channel depends on the next one and
+    /// the last channel depends on the first.
+    channels: Vec<ChannelKey>,
+
+    /// Threads that cause each relation. Relation at index `i` goes
+    /// from channel `i` to the next one.
+    causes: Vec<BTreeSet<ThreadKey>>,
+}
+
+/// Result of the analysis of the whole wait map.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Analysis {
+    deadlocks: Vec<DeadlockSet>,
+    doomed: BTreeSet<ThreadKey>,
+}
+
+/// Channels that wait for each other through their relations and the
+/// threads that cause these relations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadlockSet {
+    channels: BTreeSet<ChannelKey>,
+    threads: BTreeSet<ThreadKey>,
+}
+
 type GraphNodeKey = u32;
 
 /// Graph that shows relations between different channels. Used to find a
@@ -45,10 +80,11 @@ pub struct Graph {
     next_id: GraphNodeKey,
 }
 
-/// A node of the graph that may be connected to other nodes.
+/// A node of the graph that may be connected to other nodes. Relations
+/// do not own the nodes they point to so loops of relations do not leak.
 pub struct GraphNode {
     id: GraphNodeKey,
-    relations: BTreeMap<GraphNodeKey, Rc<GraphNode>>,
+    relations: RefCell<BTreeMap<GraphNodeKey, Weak<GraphNode>>>,
 }
 
 impl WaitDependency {
@@ -73,6 +109,53 @@ impl WaitDependency {
     }
 }
 
+impl Cycle {
+
+    /// Channels in the loop. Each channel depends on the next one and
+    /// the last channel depends on the first.
+    pub fn channels(&self) -> &[ChannelKey] {
+        &self.channels
+    }
+
+    /// Threads that cause each relation. Relation at index `i` goes
+    /// from channel `i` to the next one.
+    pub fn causes(&self) -> &[BTreeSet<ThreadKey>] {
+        &self.causes
+    }
+}
+
+impl Analysis {
+
+    /// Sets of deadlocked channels ordered by their lowest channel key.
+    pub fn deadlocks(&self) -> &[DeadlockSet] {
+        &self.deadlocks
+    }
+
+    /// Threads that wait for a deadlocked channel directly or through
+    /// relations of other channels but are not part of any deadlock.
+    pub fn doomed(&self) -> &BTreeSet<ThreadKey> {
+        &self.doomed
+    }
+
+    /// Whether no deadlocks were found.
+    pub fn is_clean(&self) -> bool {
+        self.deadlocks.is_empty()
+    }
+}
+
+impl DeadlockSet {
+
+    /// Channels that wait for each other.
+    pub fn channels(&self) -> &BTreeSet<ChannelKey> {
+        &self.channels
+    }
+
+    /// Threads that cause relations between the channels of the set.
+    pub fn threads(&self) -> &BTreeSet<ThreadKey> {
+        &self.threads
+    }
+}
+
 impl WaitMap {
 
     /// Create new wait map.
@@ -90,105 +173,77 @@ impl WaitMap {
         let added = if present {
             false
         } else {
-            self.chan.insert(key.clone(), waiters.clone());
+            self.chan.insert(key, waiters.clone());
             self.chan_to_graph.insert(
-                key.clone(), self.graph.new_node());
+                key, self.graph.new_node());
             true
         };
 
         // Add to thread map.
         for thread in waiters.iter() {
-            // Get set that contains channels connected to thread.
-            let set = self.thr.get_mut(&thread);
-            let set = if set.is_none() {
-                let btreeset = BTreeSet::new();
-                self.thr.insert(thread.clone(), btreeset);
-                self.thr.get_mut(&thread).unwrap()
-            } else {
-                set.unwrap()
-            };
-
             // Save this channel as one that thread is connected to.
-            set.insert(key.clone());
+            self.thr.entry(*thread).or_default().insert(key);
         }
 
         added
     }
 
     /// Try removing channel from the graph. It is only removed if it
-    /// has no waiters in it.
+    /// has no waiters in it. All relations to and from the channel are
+    /// removed with it.
     ///
     /// True is returned on successful remove and false if there was
-    /// some waiters in it and thus channel remains. None is returned
-    /// if channel was not found.
-    pub fn remove_channel(&mut self, key: &ChannelKey) -> Option<bool> {
-        if !self.chan.contains_key(key) {
-            return None;
-        }
-
-        if !self.chan.get(key).unwrap().is_empty() {
-            return Some(false);
+    /// some waiters in it and thus channel remains. `UnknownChannel` error
+    /// is returned if channel was not found.
+    pub fn remove_channel(&mut self, key: &ChannelKey) -> Result<bool, Error> {
+        match self.chan.get(key) {
+            Some(waiters) => if !waiters.is_empty() {
+                return Ok(false);
+            },
+            None => return Err(Error::UnknownChannel(*key)),
         }
 
         self.chan.remove(key);
-        Some(true)
+        if let Some(node) = self.chan_to_graph.remove(key) {
+            for other in self.chan_to_graph.values() {
+                other.remove_relation(&node);
+            }
+        }
+        self.causes.retain(|(from, to), _| from != key && to != key);
+        Ok(true)
     }
 
-    /// Add new waiter to registered channel. Returns false if channel
-    /// is not registered. In this case the channel gets registered
-    /// first and waiter is added then. Still false is returned.
-    /// True is returned otherwise, when channel already existed.
-    pub fn add_waiter(&mut self, key: ChannelKey, waiter: ThreadKey) -> bool {
-        let success = if self.chan.contains_key(&key) {
-            self.chan.get_mut(&key).unwrap().insert(waiter.clone());
-            true
-        } else {
-            let mut waiters = BTreeSet::new();
-            waiters.insert(waiter);
-            self.chan.insert(key, waiters);
-            false
+    /// Add new waiter to registered channel. `UnknownChannel` error is
+    /// returned if channel is not registered.
+    pub fn add_waiter(&mut self, key: ChannelKey, waiter: ThreadKey)
+            -> Result<(), Error> {
+        match self.chan.get_mut(&key) {
+            Some(waiters) => waiters.insert(waiter),
+            None => return Err(Error::UnknownChannel(key)),
         };
 
-        if !success {
-            return false;
-        }
-
-        // Register thread to channel map for this thread if it is not
-        // yet created.
-        if !self.thr.get(&waiter).is_some() {
-            let mut set = BTreeSet::new();
-            set.insert(key.clone());
-            self.thr.insert(waiter.clone(), set);
-        }
-        self.thr.get_mut(&waiter).unwrap().insert(key);
+        // Register thread to channel map, creating the entry for this
+        // thread if it is not yet present.
+        self.thr.entry(waiter).or_default().insert(key);
 
-        true
+        Ok(())
     }
 
     /// Remove waiter from the channel.
     /// Returns true if waiter was found and false
-    /// otherwise.
-    pub fn remove_waiter(&mut self, key: ChannelKey, waiter: ThreadKey) -> bool {
-        let success = if self.chan.contains_key(&key) {
-            let present = {
-                let set = self.chan.get_mut(&key).unwrap();
-                let present = set.remove(&waiter);
-
-                present
-            };
-
-            present
-        } else {
-            false
+    /// otherwise. `UnknownChannel` error is returned if channel is not
+    /// registered.
+    pub fn remove_waiter(&mut self, key: ChannelKey, waiter: ThreadKey)
+            -> Result<bool, Error> {
+        let present = match self.chan.get_mut(&key) {
+            Some(set) => set.remove(&waiter),
+            None => return Err(Error::UnknownChannel(key)),
         };
 
-        if !success {
-            return false;
+        if present {
+            self.thr.get_mut(&waiter).unwrap().remove(&key);
         }
-
-        self.thr.get_mut(&waiter).unwrap().remove(&key);
-
-        true
+        Ok(present)
     }
 
     /// Remove thread from all channels.
@@ -197,7 +252,7 @@ impl WaitMap {
     /// false if it was not found.
     pub fn remove_thread(&mut self, key: &ThreadKey) -> bool {
         // Collect all channels to remove thread from.
-        let channels = self.thr.get(key);
+        let channels = self.thr.remove(key);
         if channels.is_none() {
             return false;
         }
@@ -220,55 +275,251 @@ impl WaitMap {
         &self.thr
     }
 
-    /// Create new relation between channels.
+    /// Create new relation between channels. The relation means that
+    /// `from` channel cannot be signaled because the `cause` thread
+    /// waits for a signal from `to` channel.
     ///
-    /// Returns true if relation successfully created.
-    /// False is returned when channel was not found by the key.
-    /// Err is returned when the relation forms a loop and the changes
-    /// are reverted.
+    /// `UnknownChannel` error is returned when channel was not found by
+    /// the key. `Deadlock` error with the found loop is returned when the
+    /// relation forms a loop. The relation is not created in this case.
     pub fn add_channel_relation(&mut self, to: &ChannelKey,
-            from: &ChannelKey) -> Result<bool, ()> {
-        let all_exist = {
-            let to_exists = self.chan_to_graph.contains_key(to);
-            let from_exists = self.chan_to_graph.contains_key(from);
-            to_exists && from_exists
-        };
+            from: &ChannelKey, cause: &ThreadKey) -> Result<(), Error> {
+        let to_node = self.graph_node(to)?;
+        let from_node = self.graph_node(from)?;
 
-        if !all_exist {
-            return Ok(false);
+        if let Err(path) = from_node.add_relation(to_node) {
+            return Err(Error::Deadlock(self.cycle(&path, cause)));
         }
 
-        let to = self.chan_to_graph.get(to).unwrap();
-        let from = self.chan_to_graph.get(from).unwrap();
+        let key = (*from, *to);
+        self.causes.entry(key).or_default().insert(*cause);
+        Ok(())
+    }
 
-        match from.add_relation(&to) {
-            Ok(_)   => Ok(true),
-            Err(()) => Err(())
+    /// Create new relation between channels without checking for loops.
+    /// It is used to restore the wait map that was built elsewhere. Call
+    /// `analyze` to find the deadlocks then.
+    ///
+    /// `UnknownChannel` error is returned when channel was not found by
+    /// the key.
+    pub(crate) fn add_channel_relation_unchecked(&mut self, to: &ChannelKey,
+            from: &ChannelKey, cause: &ThreadKey) -> Result<(), Error> {
+        let to_node = self.graph_node(to)?;
+        self.graph_node(from)?.relations.borrow_mut()
+                .insert(to_node.id, Rc::downgrade(to_node));
+
+        self.causes.entry((*from, *to)).or_default()
+                .insert(*cause);
+        Ok(())
+    }
+
+    /// Analyze the whole graph of channel relations. Each strongly
+    /// connected set of channels, including single channel that depends
+    /// on itself, is a deadlock.
+    pub fn analyze(&self) -> Analysis {
+        // Relations from each channel.
+        let mut graph: BTreeMap<ChannelKey, Vec<ChannelKey>> =
+                self.chan_to_graph.keys()
+                .map(|key| (*key, Vec::new()))
+                .collect();
+        for (from, to) in self.causes.keys() {
+            graph.entry(*from).or_default().push(*to);
+            graph.entry(*to).or_default();
+        }
+
+        let mut deadlocks = Vec::new();
+        for channels in strongly_connected(&graph) {
+            let first = channels[0];
+            if channels.len() == 1 && !graph[&first].contains(&first) {
+                continue;
+            }
+
+            let channels: BTreeSet<ChannelKey> = channels.into_iter().collect();
+            let threads = self.causes.iter()
+                    .filter(|((from, to), _)|
+                            channels.contains(from) && channels.contains(to))
+                    .flat_map(|(_, causes)| causes.iter().cloned())
+                    .collect();
+            deadlocks.push(DeadlockSet {
+                channels,
+                threads,
+            });
         }
+        deadlocks.sort_by(|a, b| a.channels.iter().next()
+                .cmp(&b.channels.iter().next()));
+
+        // Channels from which deadlocked ones can be reached.
+        let mut reverse: BTreeMap<ChannelKey, Vec<ChannelKey>> =
+                BTreeMap::new();
+        for (from, to) in self.causes.keys() {
+            reverse.entry(*to).or_default().push(*from);
+        }
+        let mut blocked = BTreeSet::new();
+        let mut next: Vec<ChannelKey> = deadlocks.iter()
+                .flat_map(|set| set.channels.iter().cloned())
+                .collect();
+        while let Some(channel) = next.pop() {
+            if blocked.insert(channel) {
+                if let Some(sources) = reverse.get(&channel) {
+                    next.extend(sources.iter().cloned());
+                }
+            }
+        }
+
+        let in_deadlock: BTreeSet<ThreadKey> = deadlocks.iter()
+                .flat_map(|set| set.threads.iter().cloned())
+                .collect();
+        let doomed = self.chan.iter()
+                .filter(|(channel, _)| blocked.contains(*channel))
+                .flat_map(|(_, waiters)| waiters.iter().cloned())
+                .filter(|thread| !in_deadlock.contains(thread))
+                .collect();
+
+        Analysis {
+            deadlocks,
+            doomed,
+        }
+    }
+
+    /// Threads that cause the relation between channels. None if there is
+    /// no such relation.
+    pub fn relation_causes(&self, to: &ChannelKey, from: &ChannelKey)
+            -> Option<&BTreeSet<ThreadKey>> {
+        self.causes.get(&(*from, *to))
+    }
+
+    /// Remove all relations that go from given channel to any other.
+    ///
+    /// `UnknownChannel` error is returned if channel was not found.
+    pub fn remove_channel_relations(&mut self, from: &ChannelKey)
+            -> Result<(), Error> {
+        self.graph_node(from)?.clear_relations();
+        self.causes.retain(|(f, _), _| f != from);
+        Ok(())
     }
 
     /// Remove channel relations.
     ///
-    /// Return None if one of the channels was not found.
     /// Return true if relation was deleted or false if it didn't exist.
+    /// `UnknownChannel` error is returned if one of the channels was not
+    /// found.
     pub fn remove_channel_relation(&mut self, to: &ChannelKey,
         from: &ChannelKey
-    ) -> Option<bool> {
-        let all_exist = {
-            let to_exists = self.chan_to_graph.contains_key(to);
-            let from_exists = self.chan_to_graph.contains_key(from);
-            to_exists && from_exists
+    ) -> Result<bool, Error> {
+        let to_node = self.graph_node(to)?;
+        let from_node = self.graph_node(from)?;
+
+        let removed = from_node.remove_relation(to_node);
+        self.causes.remove(&(*from, *to));
+        Ok(removed)
+    }
+
+    /// Build the cycle from the loop of graph nodes. The first relation
+    /// of the loop is the one that was refused and so it is caused by
+    /// given thread.
+    fn cycle(&self, path: &[GraphNodeKey], cause: &ThreadKey) -> Cycle {
+        let channel_of = |id: &GraphNodeKey| {
+            self.chan_to_graph.iter()
+                .find(|(_, node)| node.id == *id)
+                .map(|(key, _)| *key)
+                .unwrap()
         };
+        let channels: Vec<ChannelKey> = path.iter().map(channel_of).collect();
+
+        let mut causes = Vec::with_capacity(channels.len());
+        for i in 0..channels.len() {
+            let from = &channels[i];
+            let to = &channels[(i + 1) % channels.len()];
+            let set = if i == 0 {
+                let mut set = BTreeSet::new();
+                set.insert(*cause);
+                set
+            } else {
+                self.relation_causes(to, from).cloned().unwrap_or_default()
+            };
+            causes.push(set);
+        }
 
-        if !all_exist {
-            return None;
+        Cycle {
+            channels,
+            causes,
         }
+    }
 
-        let to = self.chan_to_graph.get(to).unwrap();
-        let from = self.chan_to_graph.get(from).unwrap();
+    /// Graph node of the channel.
+    fn graph_node(&self, key: &ChannelKey) -> Result<&Rc<GraphNode>, Error> {
+        match self.chan_to_graph.get(key) {
+            Some(node) => Ok(node),
+            None => Err(Error::UnknownChannel(*key)),
+        }
+    }
+}
 
-        Some(from.remove_relation(to))
+/// Strongly connected components of the graph found by the Tarjan's
+/// algorithm. Graph must contain all nodes that relations point to.
+fn strongly_connected(graph: &BTreeMap<ChannelKey, Vec<ChannelKey>>)
+        -> Vec<Vec<ChannelKey>> {
+    let mut index: BTreeMap<ChannelKey, usize> = BTreeMap::new();
+    let mut low: BTreeMap<ChannelKey, usize> = BTreeMap::new();
+    let mut stack = Vec::new();
+    let mut on_stack = BTreeSet::new();
+    let mut components = Vec::new();
+
+    for root in graph.keys() {
+        if index.contains_key(root) {
+            continue;
+        }
+
+        // Nodes being visited with the number of the next relation to
+        // follow. Loop is used instead of recursion to not overflow the
+        // stack on long chains of channels.
+        let mut visits = vec![(*root, 0)];
+        index.insert(*root, index.len());
+        low.insert(*root, index[root]);
+        stack.push(*root);
+        on_stack.insert(*root);
+
+        while let Some((node, i)) = visits.pop() {
+            let relations = &graph[&node];
+            if i < relations.len() {
+                visits.push((node, i + 1));
+                let next = relations[i];
+                if !index.contains_key(&next) {
+                    let n = index.len();
+                    index.insert(next, n);
+                    low.insert(next, n);
+                    stack.push(next);
+                    on_stack.insert(next);
+                    visits.push((next, 0));
+                } else if on_stack.contains(&next) {
+                    let next_index = index[&next];
+                    let node_low = low.get_mut(&node).unwrap();
+                    *node_low = (*node_low).min(next_index);
+                }
+                continue;
+            }
+
+            // All relations of the node are followed.
+            if low[&node] == index[&node] {
+                let mut component = Vec::new();
+                loop {
+                    let member = stack.pop().unwrap();
+                    on_stack.remove(&member);
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+            if let Some((parent, _)) = visits.last() {
+                let node_low = low[&node];
+                let parent_low = low.get_mut(parent).unwrap();
+                *parent_low = (*parent_low).min(node_low);
+            }
+        }
     }
+    components
 }
 
 impl Graph {
@@ -299,56 +550,67 @@ impl GraphNode {
     /// Add new relation.
     ///
     /// Returns true on success and false if node is already present.
-    /// Error occurs if new relation forms a loop.
-    pub fn add_relation(&self, node: &Rc<GraphNode>) -> Result<bool, ()> {
-        let _self = unsafe { &mut *(self as *const _ as *mut GraphNode) };
-        if self.relation_exists(&node) {
+    /// Error occurs if new relation forms a loop. Keys of the nodes in
+    /// the loop are returned in the error, starting from this node.
+    pub fn add_relation(&self, node: &Rc<GraphNode>)
+            -> Result<bool, Vec<GraphNodeKey>> {
+        if self.relation_exists(node) {
             return Ok(false);
         }
 
-        _self.relations.insert(node.id.clone(), node.clone());
-        if self.path_has_loop() {
-            // Revert changes and return error.
-            _self.relations.remove(&node.id);
-            return Err(());
+        if let Some(path) = self.find_loop(node) {
+            return Err(path);
         }
 
+        self.relations.borrow_mut().insert(node.id, Rc::downgrade(node));
         Ok(true)
     }
 
-    /// Check whether teh path that contains this node has a loop.
-    fn path_has_loop(&self) -> bool {
-        // To check whether there is a loop we need to take each path and
-        // follow it to the end. If any of the vertices is repeated then the
-        // loop exists.
-
-        // Set of nodes we already gone through.
-        let mut nodes = BTreeSet::new();
+    /// Find the loop that would be formed by the relation from this node
+    /// to the given one. The loop exists if this node is reachable from
+    /// given node.
+    fn find_loop(&self, node: &Rc<GraphNode>) -> Option<Vec<GraphNodeKey>> {
+        // Nodes we already gone through with the node we came from.
+        let mut parents = BTreeMap::new();
+        parents.insert(node.id, node.id);
         // Next nodes to follow through.
         let mut next_nodes = LinkedList::new();
-        next_nodes.push_back(self);
-        loop {
-            let cur = next_nodes.pop_front();
-            if cur.is_none() {
-                // All path was gone through and no loop was found.
-                return false;
+        next_nodes.push_back(node.clone());
+
+        while let Some(cur) = next_nodes.pop_front() {
+            if cur.id == self.id {
+                // Restore the path back to the given node.
+                let mut path = LinkedList::new();
+                let mut id = cur.id;
+                while id != node.id {
+                    id = parents[&id];
+                    path.push_front(id);
+                }
+                path.push_front(self.id);
+                return Some(path.into_iter().collect());
             }
-            let cur = cur.unwrap();
 
-            let already_present = !nodes.insert(cur.id);
-            if already_present {
-                return true;
-            }
-
-            for (_, node) in &cur.relations {
-                next_nodes.push_back(&node);
+            let relations = cur.relations.borrow();
+            for next in relations.values().filter_map(Weak::upgrade) {
+                if let Entry::Vacant(e) = parents.entry(next.id) {
+                    e.insert(cur.id);
+                    next_nodes.push_back(next);
+                }
             }
         }
+
+        // All paths were gone through and no loop was found.
+        None
     }
 
     /// Check whether this node contains relations to given node.
     pub fn relation_exists(&self, node: &Rc<GraphNode>) -> bool {
-        self.relations.contains_key(&node.id)
+        self.relations.borrow().contains_key(&node.id)
+    }
+
+    /// Remove all relations of this node.
+    pub fn clear_relations(&self) {
+        self.relations.borrow_mut().clear();
     }
 
     /// Remove relation to node.
@@ -359,9 +621,7 @@ impl GraphNode {
             return false;
         }
 
-        let _self = unsafe { &mut *(self as *const _ as *mut GraphNode) };
-
-        _self.relations.remove(&node.id);
+        self.relations.borrow_mut().remove(&node.id);
         true
     }
 }
@@ -373,9 +633,9 @@ mod tests {
     #[test]
     fn graph_loop() {
         let mut graph = Graph::new();
-        let mut n1 = graph.new_node();
-        let mut n2 = graph.new_node();
-        let mut n3 = graph.new_node();
+        let n1 = graph.new_node();
+        let n2 = graph.new_node();
+        let n3 = graph.new_node();
 
         assert!(n1.add_relation(&n2).is_ok());
         assert!(n2.add_relation(&n3).is_ok());
@@ -401,13 +661,24 @@ mod tests {
         let c12 = 1;
         let c23 = 2;
         let c31 = 3;
-        wm.add_channel(c12.clone(), c12w);
-        wm.add_channel(c23.clone(), c23w);
-        wm.add_channel(c31.clone(), c31w);
+        wm.add_channel(c12, c12w);
+        wm.add_channel(c23, c23w);
+        wm.add_channel(c31, c31w);
+
+        assert!(wm.add_channel_relation(&c12, &c23, &2).is_ok());
+        assert!(wm.add_channel_relation(&c23, &c31, &3).is_ok());
 
-        assert!(wm.add_channel_relation(&c12, &c23).is_ok());
-        assert!(wm.add_channel_relation(&c23, &c31).is_ok());
-        assert!(wm.add_channel_relation(&c31, &c12).is_err());
+        let cycle = match wm.add_channel_relation(&c31, &c12, &1) {
+            Err(Error::Deadlock(cycle)) => cycle,
+            _ => panic!("loop was not detected"),
+        };
+        assert_eq!(cycle.channels(), &[c12, c31, c23]);
+        assert!(cycle.causes()[0].contains(&1));
+        assert!(cycle.causes()[1].contains(&3));
+        assert!(cycle.causes()[2].contains(&2));
+
+        // Refused relation is not registered.
+        assert!(wm.relation_causes(&c31, &c12).is_none());
     }
 
     #[test]
@@ -419,8 +690,61 @@ mod tests {
         c12w.insert(2);
 
         let c12 = 1;
-        wm.add_channel(c12.clone(), c12w);
+        wm.add_channel(c12, c12w);
+
+        assert!(wm.add_channel_relation(&c12, &c12, &1).is_err());
+    }
 
-        assert!(wm.add_channel_relation(&c12, &c12).is_err());
+    #[test]
+    fn wait_map_analyze() {
+        let mut wm = WaitMap::new();
+        let waiters = |threads: &[ThreadKey]| -> BTreeSet<ThreadKey> {
+            threads.iter().cloned().collect()
+        };
+        wm.add_channel(1, waiters(&[1, 4]));
+        wm.add_channel(2, waiters(&[2]));
+        wm.add_channel(3, waiters(&[3]));
+        wm.add_channel(5, waiters(&[5]));
+        wm.add_channel(6, waiters(&[6]));
+        wm.add_channel(7, waiters(&[7]));
+
+        assert!(wm.analyze().is_clean());
+
+        // Loop 1 <-> 2, channel 3 depends on it and 6 depends on itself.
+        wm.add_channel_relation_unchecked(&1, &2, &1).unwrap();
+        wm.add_channel_relation_unchecked(&2, &1, &2).unwrap();
+        wm.add_channel_relation_unchecked(&1, &3, &3).unwrap();
+        wm.add_channel_relation_unchecked(&6, &6, &6).unwrap();
+        wm.add_channel_relation_unchecked(&5, &7, &7).unwrap();
+        assert!(wm.add_channel_relation_unchecked(&9, &1, &1).is_err());
+
+        let analysis = wm.analyze();
+        assert_eq!(analysis.deadlocks().len(), 2);
+        let set = &analysis.deadlocks()[0];
+        assert_eq!(set.channels(), &waiters(&[1, 2]));
+        assert_eq!(set.threads(), &waiters(&[1, 2]));
+        assert_eq!(analysis.deadlocks()[1].channels(), &waiters(&[6]));
+        assert_eq!(analysis.doomed(), &waiters(&[3, 4]));
+
+        // Loops do not keep the nodes alive.
+        let node = Rc::downgrade(wm.graph_node(&6).unwrap());
+        assert_eq!(node.strong_count(), 1);
+        drop(wm);
+        assert!(node.upgrade().is_none());
+    }
+
+    #[test]
+    fn graph_no_loop_in_diamond() {
+        let mut graph = Graph::new();
+        let n1 = graph.new_node();
+        let n2 = graph.new_node();
+        let n3 = graph.new_node();
+        let n4 = graph.new_node();
+
+        assert!(n1.add_relation(&n2).is_ok());
+        assert!(n1.add_relation(&n3).is_ok());
+        assert!(n2.add_relation(&n4).is_ok());
+        assert!(n3.add_relation(&n4).is_ok());
+        assert_eq!(n4.add_relation(&n1), Err(vec![n4.id, n1.id, n2.id]));
     }
 }